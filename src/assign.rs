@@ -1,383 +1,156 @@
-use fnv::{FnvHashMap, FnvHashSet};
+use fnv::FnvHashMap;
 use std::collections::BTreeSet;
 
 use engine::controller::*;
 use engine::location::*;
 use engine::unit::*;
 use navigate::*;
+use rocket::*;
+use solver::*;
+use task::*;
 
-#[derive(Debug, Eq, PartialEq)]
-enum Type { Star, Prime }
 type Karbonite = FnvHashMap<MapLocation, u32>;
 
+const INFEASIBLE: i32 = 1000;
+const PRIORITY_WEIGHT: i32 = 3;
+
 fn loc(unit: &Unit) -> MapLocation {
     unit.location().map_location().unwrap()
 }
 
-pub fn assign_rockets(nav: &mut Navigator, gc: &GameController, fin_rockets: &Vec<Unit>,
-    workers: &Vec<Unit>, knights: &Vec<Unit>, rangers: &Vec<Unit>, healers: &Vec<Unit>) -> FnvHashSet<u16> {
-
-    let mut boarding = FnvHashSet::default();
-    let mut assignments = FnvHashSet::default();
-
-    let worker_spots = fin_rockets.iter()
-        .filter(|rocket| {
-            let garrison = rocket.structure_garrison().unwrap();
-            garrison.iter().all(|&unit| {
-                gc.unit(unit).unwrap().unit_type() != UnitType::Worker
-            }) && garrison.len() < rocket.structure_max_capacity().unwrap()
-        });
-
-    for spot in worker_spots {
-        let closest = workers.iter()
-            .filter(|worker| !boarding.contains(&worker.id()))
-            .min_by_key(|worker| {
-                nav.moves_between(&loc(worker), &loc(spot))
+pub fn assign_rockets(nav: &mut Navigator, gc: &GameController, manifest: &RocketManifest, fin_rockets: &Vec<Unit>,
+    workers: &Vec<Unit>, knights: &Vec<Unit>, rangers: &Vec<Unit>, mages: &Vec<Unit>, healers: &Vec<Unit>) -> FnvHashMap<UnitID, MapLocation> {
+
+    let mut boarding = FnvHashMap::default();
+
+    // Seats of the same type and priority in a rocket form one task
+    let mut slots: Vec<(&Unit, i32, Option<UnitType>)> = Vec::new();
+    let mut capacities = Vec::new();
+    for rocket in fin_rockets {
+        let garrison = garrison_types(gc, rocket);
+        let capacity = rocket.structure_max_capacity().unwrap();
+        for (priority, seat) in manifest.open(&garrison, capacity) {
+            let priority = priority as i32;
+            let existing = slots.iter().position(|&(other, p, s)| {
+                other.id() == rocket.id() && p == priority && s == seat
             });
-        if let Some(worker) = closest {
-            boarding.insert(worker.id());
-            assignments.insert(spot.id());
-            nav.navigate(worker, &loc(spot));
-        }
-    }
-
-    let soldier_spots = fin_rockets.iter()
-        .filter_map(|rocket| {
-            let garrison = rocket.structure_garrison().unwrap();
-            let worker = if assignments.contains(&rocket.id()) { 1 } else { 0 };
-            let needed = rocket.structure_max_capacity().unwrap() - garrison.len() - worker;
-            if needed > 0 { Some((needed, rocket)) } else { None }
-        }).collect::<Vec<_>>();
-
-    let soldiers = knights.iter().chain(rangers.iter()).chain(healers.iter()).collect::<Vec<_>>();
-    for (needed, spot) in soldier_spots {
-        let mut closest = soldiers.iter()
-            .filter(|soldier| !boarding.contains(&soldier.id()))
-            .collect::<Vec<_>>();
-        closest.sort_by_key(|soldier| {
-            nav.moves_between(&loc(soldier), &loc(&spot))
-        });
-        for soldier in closest.iter().take(needed) {
-            boarding.insert(soldier.id());
-            nav.navigate(soldier, &loc(spot));
+            match existing {
+                Some(index) => capacities[index] += 1,
+                None => { slots.push((rocket, priority, seat)); capacities.push(1); }
+            }
         }
     }
 
-    boarding
-}
-
-pub fn assign_workers(nav: &mut Navigator, workers: &Vec<Unit>, karbonite: &Karbonite,
-    un_facts: &Vec<Unit>, fin_facts: &Vec<Unit>, un_rockets: &Vec<Unit>) {
-
-    if workers.len() <= 0 { return }
-    let karbonite = karbonite.keys().collect::<Vec<_>>();
-    let un_facts = un_facts.iter().map(|fact| loc(fact)).collect::<Vec<_>>();
-    let fin_facts = fin_facts.iter()
-        .filter(|fact| fact.health() < fact.max_health())
-        .map(|fact| loc(fact))
+    let units = workers.iter()
+        .chain(knights.iter())
+        .chain(rangers.iter())
+        .chain(mages.iter())
+        .chain(healers.iter())
         .collect::<Vec<_>>();
-    let un_rockets = un_rockets.iter().map(|rocket| loc(rocket)).collect::<Vec<_>>();
 
-    let mut locations = Vec::new();
-    let mut optimize = Vec::new();
-    for worker in workers {
-        let mut row = Vec::new();
-        let worker_loc = loc(worker);
-        for location in &karbonite {
-            let priority = 5 + nav.moves_between(&worker_loc, &location) as i16;
-            row.push(priority);
-        }
-        for location in &un_facts {
-            let neighbors = nav.neighbors(&location);
-            let priority = nav.moves_between(&worker_loc, &location) as i16;
-            for _ in 0..neighbors { row.push(priority); locations.push(location); }
-        }
-        for location in &fin_facts {
-            let neighbors = nav.neighbors(&location) - 1;
-            let priority = 10 + nav.moves_between(&worker_loc, &location) as i16;
-            for _ in 0..neighbors { row.push(priority); locations.push(location); }
-        }
-        for location in &un_rockets {
-            let neighbors = nav.neighbors(&location);
-            let priority = nav.moves_between(&worker_loc, &location) as i16;
-            for _ in 0..neighbors { row.push(priority); locations.push(location); }
-        }
-        optimize.push(row);
-    }
+    if units.len() == 0 || slots.len() == 0 { return boarding }
 
-    let k = karbonite.len();
-    if optimize.len() > 0 && optimize[0].len() > 0 {
-        for (worker, location) in hungarian(optimize) {
-            if location < k {
-                nav.navigate(&workers[worker], &karbonite[location]);
+    let mut optimize = Vec::new();
+    for unit in &units {
+        let unit_loc = loc(unit);
+        let row = slots.iter().map(|&(rocket, priority, seat)| {
+            let allowed = match seat {
+                Some(seat) => seat == unit.unit_type(),
+                None => unit.unit_type() != UnitType::Worker,
+            };
+            if allowed {
+                nav.moves_between(&unit_loc, &loc(rocket)) + priority*PRIORITY_WEIGHT
             } else {
-                nav.navigate(&workers[worker], &locations[location - k]);
+                INFEASIBLE
             }
-        }
-    }
-}
-
-fn hungarian(mut matrix: Vec<Vec<i16>>) -> FnvHashMap<usize, usize> {
-    let rows = matrix.len();
-    let cols = matrix[0].len();
-    let target = if rows < cols { rows } else { cols };
-
-    // Reduce rows
-    for mut row in &mut matrix {
-        let min = row.iter().min().unwrap().clone();
-        row.iter_mut().for_each(|cost| *cost -= min);
+        }).collect::<Vec<_>>();
+        optimize.push(row);
     }
 
-    let mut mask = FnvHashMap::default();
-    let mut row_cover = vec![false; rows];
-    let mut col_cover = vec![false; cols];
-
-    // Star zeros
-    for row in 0..rows {
-        for col in 0..cols {
-            if matrix[row][col] == 0
-            && !(row_cover[row] || col_cover[col]) {
-                mask.insert((row, col), Type::Star);
-                row_cover[row] = true;
-                col_cover[col] = true;
-            }
-        }
+    for (unit, slot) in assign(&optimize, &capacities).into_iter().enumerate() {
+        let slot = match slot { Some(slot) => slot, None => continue };
+        if optimize[unit][slot] >= INFEASIBLE { continue }
+        let (rocket, _, _) = slots[slot];
+        boarding.insert(units[unit].id(), loc(rocket));
+        nav.navigate(units[unit], &loc(rocket));
     }
 
-    // Reset cover
-    row_cover.iter_mut().for_each(|cov| *cov = false);
-    col_cover.iter_mut().for_each(|cov| *cov = false);
-    let mut verify = true;
-
-    loop {
-
-        // Count cover
-        if verify {
-            for row in 0..rows {
-                for col in 0..cols {
-                    if let Some(&Type::Star) = mask.get(&(row, col)) {
-                        col_cover[col] = true;
-                    }
-                }
-            }
-
-            if col_cover.iter().filter(|&&cov| cov).count() == target {
-                return mask.into_iter()
-                    .filter(|&(_, ref t)| t == &Type::Star)
-                    .map(|((row, col), _)| (row, col))
-                    .collect::<FnvHashMap<_, _>>()
-            }
-        }
-
-        // Find uncovered zero
-        let mut uncovered = None;
-        for row in 0..rows {
-            if uncovered != None { break }
-            for col in 0..cols {
-                if matrix[row][col] == 0
-                && mask.get(&(row, col)).is_none()
-                && !row_cover[row]
-                && !col_cover[col] {
-                    uncovered = Some((row, col));
-                    break
-                }
-            }
-        }
-
-        // Add and subtract minimum uncovered value
-        if let None = uncovered {
-            let mut min = i16::max_value();
-            for row in 0..rows {
-                for col in 0..cols {
-                    if row_cover[row] || col_cover[col] { continue }
-                    let value = matrix[row][col];
-                    min = if value < min { value } else { min };
-                }
-            }
-
-            for row in 0..rows {
-                for col in 0..cols {
-                    if row_cover[row] { matrix[row][col] += min }
-                    if !col_cover[col] { matrix[row][col] -= min }
-                }
-            }
-
-            verify = false;
-            continue
-        }
-
-        let (row, col) = uncovered.unwrap();
-        mask.insert((row, col), Type::Prime);
-
-        let starred = (0..cols).filter(|&col| {
-            mask.get(&(row, col)) == Some(&Type::Star)
-            && matrix[row][col] == 0
-        }).nth(0);
-
-        if let Some(adj) = starred {
-            row_cover[row] = true;
-            col_cover[adj] = false;
-            verify = false;
-            continue
-        }
+    boarding
+}
 
-        // Alternating path of Stars and Primes
-        let mut path = vec![(row, col)];
-        loop {
-            let (_, prev_col) = path[path.len() - 1];
-            let next_star = (0..rows).filter(|&row| {
-                mask.get(&(row, prev_col)) == Some(&Type::Star)
-            }).nth(0);
+pub fn assign_workers(gc: &GameController, nav: &mut Navigator, auction: &mut Auction<UnitID, Task>,
+    workers: &Vec<Unit>, karbonite: &Karbonite, un_facts: &Vec<Unit>, fin_facts: &Vec<Unit>,
+    un_rockets: &Vec<Unit>, blueprints: &Vec<(UnitType, MapLocation)>,
+    boarding: &FnvHashMap<UnitID, MapLocation>) -> FnvHashMap<UnitID, Task> {
 
-            if let None = next_star { break }
-            let star_row = next_star.unwrap();
-            path.push((star_row, prev_col));
+    let mut assigned = FnvHashMap::default();
+    let shelters = un_facts.iter().chain(fin_facts.iter()).map(|fact| loc(fact)).collect::<Vec<_>>();
 
-            let prime_col = (0..cols).filter(|&col| {
-                mask.get(&(star_row, col)) == Some(&Type::Prime)
-            }).nth(0).unwrap();
-            path.push((star_row, prime_col));
-        }
-
-        // Augment path
-        for (row, col) in path {
-            match mask.get(&(row, col)) {
-                None => continue,
-                Some(&Type::Star) => mask.remove(&(row, col)),
-                Some(&Type::Prime) => mask.insert((row, col), Type::Star),
-            };
+    // Boarding and fleeing override everything else
+    let mut free = Vec::new();
+    for worker in workers {
+        let worker_loc = loc(worker);
+        if let Some(&rocket) = boarding.get(&worker.id()) {
+            assigned.insert(worker.id(), Task::Board(rocket));
+        } else if shelters.len() > 0 && threatened(gc, worker) {
+            let shelter = *shelters.iter()
+                .min_by_key(|shelter| nav.moves_between(&worker_loc, shelter))
+                .unwrap();
+            assigned.insert(worker.id(), Task::Flee(shelter));
+            nav.navigate(worker, &shelter);
+        } else {
+            free.push(worker);
         }
-
-        // Reset cover
-        row_cover.iter_mut().for_each(|cov| *cov = false);
-        col_cover.iter_mut().for_each(|cov| *cov = false);
-
-        // Erase primes
-        mask.retain(|_, t| t != &mut Type::Prime);
-        verify = true;
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use karbonite::*;
-
-    #[test]
-    fn test_basic() {
-        let matrix = vec![
-            vec![1, 1, 1],
-            vec![1, 1, 1],
-            vec![1, 1, 1],
-        ];
-        println!("{:#?}", hungarian(matrix));
+    if free.len() == 0 { return assigned }
+
+    // Karbonite fits one worker, structures fit one per open neighbor
+    let mut tasks = Vec::new();
+    let mut capacities = Vec::new();
+    for &location in karbonite.keys() {
+        tasks.push((Task::Harvest(location), 5));
+        capacities.push(1);
     }
-
-    #[test]
-    fn test_sales() {
-        let matrix = vec![
-            vec![250, 400, 350],
-            vec![400, 600, 350],
-            vec![200, 400, 250],
-        ];
-        hungarian(matrix);
+    for fact in un_facts.iter().chain(un_rockets.iter()) {
+        let location = loc(fact);
+        tasks.push((Task::Build(location), 0));
+        capacities.push(nav.neighbors(&location));
     }
-
-    #[test]
-    fn test_wiki() {
-        let matrix = vec![
-            vec![0, 1, 2, 3],
-            vec![4, 5, 6, 0],
-            vec![0, 2, 4, 5],
-            vec![3, 0, 0, 9],
-        ];
-        hungarian(matrix);
+    for fact in fin_facts.iter().filter(|fact| fact.health() < fact.max_health()) {
+        let location = loc(fact);
+        tasks.push((Task::Repair(location), 10));
+        capacities.push(nav.neighbors(&location) - 1);
     }
-
-    #[test]
-    fn test_bulldozer() {
-        let matrix = vec![
-            vec![90, 75, 75, 80],
-            vec![35, 85, 55, 65],
-            vec![125, 95, 90, 105],
-            vec![45, 110, 95, 115],
-        ];
-        hungarian(matrix);
+    for &(building_type, location) in blueprints {
+        tasks.push((Task::Blueprint(building_type, location), 0));
+        capacities.push(1);
     }
+    if tasks.len() == 0 { return assigned }
 
-    #[test]
-    fn test_stack() {
-        let matrix = vec![
-            vec![2, 9, 2, 7, 1],
-            vec![6, 8, 7, 6, 1],
-            vec![4, 6, 5, 3, 1],
-            vec![4, 2, 7, 3, 1],
-            vec![5, 3, 9, 5, 1],
-        ];
-        hungarian(matrix);
-    }
-
-    #[test]
-    fn test_large() {
-        let matrix = vec![
-            vec![1,5,5,2,3,1,2,3,2,4,5,2,3,1,5,5,2,3,1,5,1,4,3,2,5],
-            vec![5,5,3,2,3,2,5,1,4,3,2,5,3,2,4,5,2,5,2,1,1,4,1,2,5],
-            vec![5,1,4,3,2,5,1,1,4,1,2,5,2,2,3,4,1,4,5,3,2,4,5,2,5],
-            vec![1,1,4,1,2,5,3,2,4,5,2,5,5,5,1,5,1,5,5,2,2,3,4,1,4],
-            vec![3,2,4,5,2,5,2,2,3,4,1,4,5,4,2,1,3,2,5,5,5,1,5,1,5],
-            vec![2,2,3,4,1,4,5,5,1,5,1,5,5,5,2,5,5,1,4,5,4,2,1,3,2],
-            vec![5,5,1,5,1,5,5,5,3,2,3,2,1,5,5,1,5,1,5,5,5,2,5,5,1],
-            vec![5,4,2,1,3,2,5,1,4,3,2,5,5,5,4,2,1,3,2,5,1,4,3,2,5],
-            vec![5,5,2,5,5,1,1,1,4,1,2,5,1,5,5,2,5,5,1,1,1,4,1,2,5],
-            vec![2,4,5,3,4,2,3,2,4,5,2,5,2,2,4,5,3,4,2,3,2,4,5,2,5],
-            vec![2,2,5,5,1,3,2,2,3,4,1,4,2,2,2,5,5,1,3,2,2,3,4,1,4],
-            vec![4,1,5,4,5,3,5,5,1,5,1,5,5,4,1,5,4,5,3,5,5,1,5,1,5],
-            vec![5,1,4,3,2,5,3,2,4,5,2,5,5,5,1,4,3,2,5,3,2,4,5,2,5],
-            vec![1,1,4,1,2,5,2,2,3,4,1,4,1,1,1,4,1,2,5,2,2,3,4,1,4],
-            vec![3,2,4,5,2,5,5,5,1,5,1,5,4,3,2,4,5,2,5,5,5,1,5,1,5],
-            vec![2,2,3,4,1,4,5,4,2,1,3,2,1,2,2,3,4,1,4,5,4,2,1,3,2],
-            vec![5,5,1,5,1,5,5,5,2,5,5,1,2,5,5,1,5,1,5,5,5,2,5,5,1],
-            vec![5,1,4,3,2,5,3,5,1,4,3,2,5,3,5,2,2,3,5,2,2,3,2,5,3],
-            vec![3,4,1,4,1,1,1,1,1,4,1,2,5,5,1,4,3,2,5,1,4,1,2,5,2],
-            vec![1,5,5,2,3,1,5,3,2,4,5,2,5,1,1,4,1,2,5,2,4,5,2,5,5],
-            vec![5,5,3,2,3,2,2,2,2,3,4,1,4,3,2,4,5,2,5,2,3,4,1,4,3],
-            vec![5,1,4,3,2,5,2,5,5,1,5,1,5,2,2,3,4,1,4,5,1,5,1,5,5],
-            vec![1,1,4,1,2,5,2,5,4,2,1,3,2,5,5,1,5,1,5,4,2,1,3,2,1],
-            vec![3,2,4,5,2,5,1,5,5,2,5,5,1,5,4,2,1,3,2,5,2,5,5,1,3],
-            vec![2,2,3,4,1,4,1,2,4,5,3,4,2,5,5,2,5,5,1,4,5,3,4,2,2],
-        ];
-        hungarian(matrix);
+    let mut optimize = Vec::new();
+    for worker in &free {
+        let worker_loc = loc(worker);
+        let row = tasks.iter()
+            .map(|&(task, priority)| priority + nav.moves_between(&worker_loc, &task.target()))
+            .collect::<Vec<_>>();
+        optimize.push(row);
     }
 
-    #[test]
-    fn test_c() {
-        let matrix = vec![
-            vec![0,0,0,0,0,0,0,0,0,0,0,0,0,0],
-            vec![53,207,256,207,231,348,348,348,231,244,244,0,0,0],
-            vec![240,33,67,33,56,133,133,133,56,33,33,0,0,0],
-            vec![460,107,200,107,122,324,324,324,122,33,33,0,0,0],
-            vec![167,340,396,340,422,567,567,567,422,442,442,0,0,0],
-            vec![167,367,307,367,433,336,336,336,433,158,158,0,0,0],
-            vec![160,20,37,20,31,70,70,70,31,22,22,0,0,0],
-            vec![200,307,393,307,222,364,364,364,222,286,286,0,0,0],
-            vec![33,153,152,153,228,252,252,252,228,78,78,0,0,0],
-            vec![93,140,185,140,58,118,118,118,58,44,44,0,0,0],
-            vec![0,7,22,7,19,58,58,58,19,0,0,0,0,0],
-            vec![67,153,241,153,128,297,297,297,128,39,39,0,0,0],
-            vec![73,253,389,253,253,539,539,539,253,36,36,0,0,0],
-            vec![73,267,270,267,322,352,352,352,322,231,231,0,0,0],
-        ];
-        println!("{:?}", hungarian(matrix));
+    let agents = free.iter().map(|worker| worker.id()).collect::<Vec<_>>();
+    let keys = tasks.iter().map(|&(task, _)| task).collect::<Vec<_>>();
+    for (worker, task) in auction.solve(&agents, &keys, &optimize, &capacities).into_iter().enumerate() {
+        if let Some(task) = task {
+            nav.navigate(free[worker], &keys[task].target());
+            assigned.insert(agents[worker], keys[task]);
+        }
     }
+    assigned
+}
 
-    #[test]
-    fn test_wikihow() {
-        let matrix = vec![
-            vec![10, 19, 8, 15, 19],
-            vec![10, 18, 7, 17, 19],
-            vec![13, 16, 9, 14, 19],
-            vec![12, 19, 8, 18, 19],
-            vec![14, 17, 10, 19, 19]
-        ];
-        hungarian(matrix);
-    }
+fn threatened(gc: &GameController, worker: &Unit) -> bool {
+    let worker_loc = loc(worker);
+    gc.sense_nearby_units_by_team(worker_loc, 50, gc.team().other())
+        .iter()
+        .filter(|en| en.unit_type().is_robot() && en.unit_type() != UnitType::Worker && en.unit_type() != UnitType::Healer)
+        .any(|en| loc(en).distance_squared_to(worker_loc) <= en.attack_range().unwrap() + 8)
 }
@@ -0,0 +1,78 @@
+use fnv::FnvHashMap;
+
+/// What karbonite gets spent on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Purpose {
+    Rocket,
+    Blueprint,
+    Replicate,
+    Produce,
+}
+
+/// Per-turn karbonite allocator. Reserves hold karbonite for one purpose
+/// so that spending in loop order can't starve it.
+#[derive(Debug)]
+pub struct Budget {
+    available: u32,
+    reserved: FnvHashMap<Purpose, u32>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Budget { available: 0, reserved: FnvHashMap::default() }
+    }
+
+    /// Starts a new turn with `karbonite` on hand and no reserves.
+    pub fn plan(&mut self, karbonite: u32) {
+        self.available = karbonite;
+        self.reserved.clear();
+    }
+
+    pub fn reserve(&mut self, purpose: Purpose, amount: u32) {
+        *self.reserved.entry(purpose).or_insert(0) += amount;
+    }
+
+    pub fn reserved(&self, purpose: Purpose) -> u32 {
+        self.reserved.get(&purpose).cloned().unwrap_or(0)
+    }
+
+    /// Karbonite `purpose` may use: its own reserve plus whatever nobody else reserved.
+    pub fn free(&self, purpose: Purpose) -> u32 {
+        let others = self.reserved.iter()
+            .filter(|&(&other, _)| other != purpose)
+            .map(|(_, &amount)| amount)
+            .sum::<u32>();
+        self.available.saturating_sub(others)
+    }
+
+    pub fn can_spend(&self, purpose: Purpose, cost: u32) -> bool {
+        self.free(purpose) >= cost
+    }
+
+    pub fn spend(&mut self, purpose: Purpose, cost: u32) {
+        self.available = self.available.saturating_sub(cost);
+        if let Some(reserve) = self.reserved.get_mut(&purpose) {
+            *reserve = reserve.saturating_sub(cost);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use budget::*;
+
+    #[test]
+    fn test_reserve() {
+        let mut budget = Budget::new();
+        budget.plan(200);
+        budget.reserve(Purpose::Rocket, 150);
+        assert!(!budget.can_spend(Purpose::Produce, 60));
+        assert!(budget.can_spend(Purpose::Produce, 50));
+        assert!(budget.can_spend(Purpose::Rocket, 200));
+
+        budget.spend(Purpose::Rocket, 150);
+        assert_eq!(budget.reserved(Purpose::Rocket), 0);
+        assert!(budget.can_spend(Purpose::Produce, 50));
+        assert!(!budget.can_spend(Purpose::Produce, 51));
+    }
+}
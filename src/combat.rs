@@ -0,0 +1,303 @@
+use fnv::FnvHashSet;
+use std::f64;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+
+// Tiles an enemy can cover by stepping once before attacking
+const STEP: f64 = 1.5;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// How much we want a target dead.
+pub fn target_value(unit_type: UnitType) -> i32 {
+    match unit_type {
+        UnitType::Mage => 5,
+        UnitType::Knight | UnitType::Ranger | UnitType::Healer => 4,
+        UnitType::Factory | UnitType::Rocket => 2,
+        UnitType::Worker => 1,
+    }
+}
+
+/// Squared distance within which `enemy` can hit a tile next turn.
+pub fn reach(enemy: &Unit) -> u32 {
+    match enemy.unit_type() {
+        UnitType::Knight | UnitType::Ranger | UnitType::Mage => {
+            let range = (enemy.attack_range().unwrap() as f64).sqrt() + STEP;
+            (range * range) as u32
+        }
+        _ => 0,
+    }
+}
+
+/// Damage `attacker` deals to `target` after knight armor.
+pub fn effective(attacker: &Unit, target: &Unit) -> i32 {
+    let defense = if target.unit_type() == UnitType::Knight { target.knight_defense().unwrap() as i32 } else { 0 };
+    i32::max(0, attacker.damage().unwrap() - defense)
+}
+
+/// Pairs ready attackers with enemies in range so that as many as possible die this turn.
+pub fn allocate(gc: &GameController, attackers: &Vec<Unit>) -> Vec<(UnitID, UnitID)> {
+    let ready = attackers.iter()
+        .filter(|unit| unit.location().is_on_map() && gc.is_attack_ready(unit.id()))
+        .collect::<Vec<_>>();
+
+    let mut seen = FnvHashSet::default();
+    let mut targets = Vec::new();
+    for attacker in &ready {
+        for enemy in gc.sense_nearby_units_by_team(loc(attacker), attacker.attack_range().unwrap(), gc.team().other()) {
+            if seen.insert(enemy.id()) { targets.push(enemy) }
+        }
+    }
+    if targets.len() == 0 { return Vec::new() }
+
+    let damage = ready.iter()
+        .map(|attacker| targets.iter()
+            .map(|target| if gc.can_attack(attacker.id(), target.id()) { Some(effective(attacker, target)) } else { None })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let health = targets.iter().map(|target| target.health() as i32).collect::<Vec<_>>();
+    let values = targets.iter().map(|target| target_value(target.unit_type())).collect::<Vec<_>>();
+
+    focus(&damage, &health, &values).into_iter()
+        .enumerate()
+        .filter_map(|(attacker, target)| target.map(|target| (ready[attacker].id(), targets[target].id())))
+        .collect()
+}
+
+/// Allocates and issues attacks, returning the units that fired.
+pub fn fire(gc: &mut GameController, attackers: &Vec<Unit>) -> Vec<UnitID> {
+    let mut fired = Vec::new();
+    for (attacker, target) in allocate(gc, attackers) {
+        if gc.can_attack(attacker, target) {
+            gc.attack(attacker, target);
+            fired.push(attacker);
+        }
+    }
+    fired
+}
+
+/// Assigns each attacker a target index. `damage[a][t]` is `None` when `t` is out of
+/// range of `a`. Kills are taken cheapest first, then leftovers pile onto the
+/// most valuable, most hurt target they can reach.
+pub fn focus(damage: &Vec<Vec<Option<i32>>>, health: &Vec<i32>, value: &Vec<i32>) -> Vec<Option<usize>> {
+    let (n, m) = (damage.len(), health.len());
+    let reach = damage.iter()
+        .map(|row| row.iter().filter(|dmg| dmg.is_some()).count())
+        .collect::<Vec<_>>();
+    let mut result = vec![None; n];
+    let mut killed = vec![false; m];
+
+    loop {
+        let mut best: Option<(usize, Vec<usize>)> = None;
+        for t in 0..m {
+            if killed[t] { continue }
+
+            // Attackers with fewer options go first so flexible ones stay free
+            let mut shooters = (0..n)
+                .filter(|&a| result[a].is_none() && damage[a][t].map_or(false, |dmg| dmg > 0))
+                .collect::<Vec<_>>();
+            shooters.sort_by_key(|&a| (reach[a], -damage[a][t].unwrap()));
+
+            let mut total = 0;
+            let mut chosen = Vec::new();
+            for a in shooters {
+                if total >= health[t] { break }
+                total += damage[a][t].unwrap();
+                chosen.push(a);
+            }
+            if total < health[t] { continue }
+
+            let better = match best {
+                Some((b, ref shots)) => (chosen.len(), -value[t]) < (shots.len(), -value[b]),
+                None => true,
+            };
+            if better { best = Some((t, chosen)) }
+        }
+
+        match best {
+            Some((t, chosen)) => {
+                for a in chosen { result[a] = Some(t) }
+                killed[t] = true;
+            }
+            None => break,
+        }
+    }
+
+    let mut remaining = health.clone();
+    for a in 0..n {
+        if result[a].is_some() { continue }
+        let target = (0..m)
+            .filter(|&t| !killed[t] && damage[a][t].is_some())
+            .max_by_key(|&t| (value[t], -remaining[t]));
+        if let Some(t) = target {
+            remaining[t] -= damage[a][t].unwrap();
+            result[a] = Some(t);
+        }
+    }
+    result
+}
+
+/// What a unit should do about a nearby fight.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Stance {
+    Engage,
+    Hold,
+    Retreat,
+}
+
+const SIMULATED: u32 = 10;
+const MARGIN: f64 = 0.2;
+
+/// A unit reduced to what matters in a skirmish.
+#[derive(Clone, Debug)]
+pub struct Fighter {
+    pub health: f64,
+    pub dps: f64,
+    pub heal: f64,
+    pub delay: u32,
+}
+
+impl Fighter {
+    /// `distance` is the squared distance to the nearest opponent.
+    pub fn new(unit: &Unit, distance: u32) -> Self {
+        let health = unit.health() as f64;
+        if !unit.unit_type().is_robot() || unit.unit_type() == UnitType::Worker {
+            return Fighter { health, dps: 0.0, heal: 0.0, delay: 0 }
+        }
+
+        // Heat drops by 10 a round, so a unit attacks 10/cooldown times per round
+        let rate = 10.0 / unit.attack_cooldown().unwrap() as f64;
+        let damage = unit.damage().unwrap() as f64;
+        let range = (unit.attack_range().unwrap() as f64).sqrt();
+        let delay = f64::min(SIMULATED as f64, f64::max(0.0, (distance as f64).sqrt() - range).ceil()) as u32;
+        if damage < 0.0 {
+            Fighter { health, dps: 0.0, heal: -damage * rate, delay }
+        } else {
+            Fighter { health, dps: damage * rate, heal: 0.0, delay }
+        }
+    }
+}
+
+/// Plays out a few rounds of both sides trading focused fire and returns the
+/// fraction of health each side has left.
+pub fn simulate(ours: &Vec<Fighter>, theirs: &Vec<Fighter>) -> (f64, f64) {
+    let mut ours = ours.clone();
+    let mut theirs = theirs.clone();
+    let start = (total(&ours), total(&theirs));
+
+    for round in 0..SIMULATED {
+        let (to_them, heal_us) = output(&ours, round);
+        let (to_us, heal_them) = output(&theirs, round);
+        strike(&mut ours, to_us, heal_us);
+        strike(&mut theirs, to_them, heal_them);
+        if ours.len() == 0 || theirs.len() == 0 { break }
+    }
+
+    let fraction = |left: f64, start: f64| if start > 0.0 { left / start } else { 0.0 };
+    (fraction(total(&ours), start.0), fraction(total(&theirs), start.1))
+}
+
+fn total(side: &Vec<Fighter>) -> f64 {
+    side.iter().map(|fighter| fighter.health).sum()
+}
+
+fn output(side: &Vec<Fighter>, round: u32) -> (f64, f64) {
+    side.iter()
+        .filter(|fighter| fighter.delay <= round)
+        .fold((0.0, 0.0), |(dps, heal), fighter| (dps + fighter.dps, heal + fighter.heal))
+}
+
+/// Damage lands on the weakest first, healing on the weakest survivor.
+fn strike(side: &mut Vec<Fighter>, mut damage: f64, heal: f64) {
+    side.sort_by(|a, b| a.health.partial_cmp(&b.health).unwrap());
+    for fighter in side.iter_mut() {
+        let dealt = f64::min(damage, fighter.health);
+        fighter.health -= dealt;
+        damage -= dealt;
+    }
+    side.retain(|fighter| fighter.health > 0.0);
+    if let Some(weakest) = side.first_mut() {
+        weakest.health += heal;
+    }
+}
+
+/// Estimates the fight within `radius` of `location` from our side.
+pub fn evaluate(gc: &GameController, location: MapLocation, radius: u32) -> Stance {
+    let friends = gc.sense_nearby_units_by_team(location, radius, gc.team());
+    let enemies = gc.sense_nearby_units_by_team(location, radius, gc.team().other());
+    let fighters = |side: &Vec<Unit>, other: &Vec<Unit>| side.iter()
+        .filter(|unit| unit.unit_type().is_robot())
+        .map(|unit| {
+            let distance = other.iter()
+                .map(|opponent| loc(unit).distance_squared_to(loc(opponent)))
+                .min()
+                .unwrap_or(u32::max_value());
+            Fighter::new(unit, distance)
+        })
+        .collect::<Vec<_>>();
+
+    let theirs = fighters(&enemies, &friends);
+    if theirs.iter().all(|fighter| fighter.dps == 0.0) { return Stance::Engage }
+    let (left, enemy_left) = simulate(&fighters(&friends, &enemies), &theirs);
+    stance(left, enemy_left)
+}
+
+pub fn stance(left: f64, enemy_left: f64) -> Stance {
+    if left - enemy_left > MARGIN {
+        Stance::Engage
+    } else if enemy_left - left > MARGIN {
+        Stance::Retreat
+    } else {
+        Stance::Hold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use combat::*;
+
+    #[test]
+    fn test_kill() {
+        // Two shots kill the weaker target, the third chips the other
+        let damage = vec![vec![Some(10), Some(10)]; 3];
+        let result = focus(&damage, &vec![20, 25], &vec![4, 4]);
+        assert_eq!(result.iter().filter(|&&t| t == Some(0)).count(), 2);
+        assert_eq!(result.iter().filter(|&&t| t == Some(1)).count(), 1);
+    }
+
+    #[test]
+    fn test_spread() {
+        // Each attacker can kill alone, so no damage is wasted on overkill
+        let damage = vec![
+            vec![Some(10), Some(10)],
+            vec![Some(10), None],
+        ];
+        let result = focus(&damage, &vec![10, 10], &vec![4, 4]);
+        assert_eq!(result, vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let damage = vec![vec![None], vec![Some(5)]];
+        let result = focus(&damage, &vec![50], &vec![4]);
+        assert_eq!(result, vec![None, Some(0)]);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let ranger = Fighter { health: 200.0, dps: 15.0, heal: 0.0, delay: 0 };
+        let healer = Fighter { health: 100.0, dps: 0.0, heal: 5.0, delay: 0 };
+
+        // Equal sides trade evenly, and a healer tips the balance
+        let (left, enemy_left) = simulate(&vec![ranger.clone(); 3], &vec![ranger.clone(); 3]);
+        assert_eq!(stance(left, enemy_left), Stance::Hold);
+        let (left, enemy_left) = simulate(&vec![ranger.clone(); 4], &vec![ranger.clone(); 2]);
+        assert_eq!(stance(left, enemy_left), Stance::Engage);
+        let (left, enemy_left) = simulate(&vec![ranger.clone(), ranger.clone()], &vec![ranger.clone(), ranger.clone(), healer]);
+        assert_eq!(stance(left, enemy_left), Stance::Retreat);
+    }
+}
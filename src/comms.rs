@@ -0,0 +1,171 @@
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+
+// Layout of the team array
+const HEADER: usize = 0;
+const COUNT: usize = 1;
+const OFFSET: usize = 2;
+const LENGTH: usize = 100;
+const VERSION: i32 = 1;
+
+// Bit widths of an encoded message
+const KIND_BITS: i32 = 4;
+const COORD_BITS: i32 = 6;
+const PAYLOAD_BITS: i32 = 15;
+const COORD_MASK: i32 = (1 << COORD_BITS) - 1;
+const PAYLOAD_MASK: i32 = (1 << PAYLOAD_BITS) - 1;
+const KIND_MASK: i32 = (1 << KIND_BITS) - 1;
+
+/// Messages passed between the Earth and Mars players.
+/// All locations refer to Mars, since that's the only planet both sides care about.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Message {
+
+    // Earth to Mars
+    Rocket { location: MapLocation, arrival: Rounds },
+    Landing { location: MapLocation },
+
+    // Mars to Earth
+    Enemy { location: MapLocation, unit_type: UnitType },
+    Karbonite { location: MapLocation, amount: u32 },
+    Safe { location: MapLocation },
+}
+
+#[derive(Debug)]
+pub struct Radio {
+    planet: Planet,
+    outbox: Vec<Message>,
+    last: Rounds,
+}
+
+impl Radio {
+    pub fn new(gc: &GameController) -> Self {
+        Radio { planet: gc.planet(), outbox: Vec::new(), last: 0 }
+    }
+
+    pub fn send(&mut self, message: Message) {
+        if !self.outbox.contains(&message) {
+            self.outbox.push(message);
+        }
+    }
+
+    /// Writes as many queued messages as fit this round, keeping the rest for later.
+    /// Enemy sightings that don't fit are dropped rather than sent stale.
+    pub fn flush(&mut self, gc: &mut GameController) {
+        let capacity = LENGTH - OFFSET;
+        let count = usize::min(capacity, self.outbox.len());
+        for (i, message) in self.outbox.drain(..count).enumerate() {
+            let _ = gc.write_team_array(OFFSET + i, encode(&message));
+        }
+        // Enemy positions are only good for the round they were seen
+        self.outbox.retain(|message| match *message {
+            Message::Enemy { .. } => false,
+            _ => true,
+        });
+        let _ = gc.write_team_array(COUNT, count as i32);
+        let _ = gc.write_team_array(HEADER, (VERSION << 16) | (gc.round() as i32 & 0xFFFF));
+    }
+
+    /// Reads the other planet's most recent snapshot, if we haven't already.
+    pub fn receive(&mut self, gc: &GameController) -> Vec<Message> {
+        let array = gc.get_team_array(self.planet.other());
+        let header = array[HEADER];
+        let round = (header & 0xFFFF) as Rounds;
+        if header >> 16 != VERSION || round <= self.last {
+            return Vec::new()
+        }
+        self.last = round;
+
+        let count = usize::min(array[COUNT] as usize, LENGTH - OFFSET);
+        array[OFFSET..OFFSET + count].iter()
+            .filter_map(|&value| decode(value))
+            .collect::<Vec<_>>()
+    }
+}
+
+fn pack(kind: i32, location: &MapLocation, payload: i32) -> i32 {
+    (kind << (2*COORD_BITS + PAYLOAD_BITS))
+    | ((location.x & COORD_MASK) << (COORD_BITS + PAYLOAD_BITS))
+    | ((location.y & COORD_MASK) << PAYLOAD_BITS)
+    | (payload & PAYLOAD_MASK)
+}
+
+fn encode(message: &Message) -> i32 {
+    match *message {
+        Message::Rocket { ref location, arrival } => pack(1, location, arrival as i32),
+        Message::Landing { ref location } => pack(2, location, 0),
+        Message::Enemy { ref location, unit_type } => pack(3, location, to_code(unit_type)),
+        Message::Karbonite { ref location, amount } => pack(4, location, u32::min(amount, PAYLOAD_MASK as u32) as i32),
+        Message::Safe { ref location } => pack(5, location, 0),
+    }
+}
+
+fn decode(value: i32) -> Option<Message> {
+    let kind = (value >> (2*COORD_BITS + PAYLOAD_BITS)) & KIND_MASK;
+    let x = (value >> (COORD_BITS + PAYLOAD_BITS)) & COORD_MASK;
+    let y = (value >> PAYLOAD_BITS) & COORD_MASK;
+    let payload = value & PAYLOAD_MASK;
+    let location = MapLocation::new(Planet::Mars, x, y);
+    match kind {
+        1 => Some(Message::Rocket { location, arrival: payload as Rounds }),
+        2 => Some(Message::Landing { location }),
+        3 => from_code(payload).map(|unit_type| Message::Enemy { location, unit_type }),
+        4 => Some(Message::Karbonite { location, amount: payload as u32 }),
+        5 => Some(Message::Safe { location }),
+        _ => None,
+    }
+}
+
+fn to_code(unit_type: UnitType) -> i32 {
+    match unit_type {
+        UnitType::Worker => 0,
+        UnitType::Knight => 1,
+        UnitType::Ranger => 2,
+        UnitType::Mage => 3,
+        UnitType::Healer => 4,
+        UnitType::Factory => 5,
+        UnitType::Rocket => 6,
+    }
+}
+
+fn from_code(code: i32) -> Option<UnitType> {
+    match code {
+        0 => Some(UnitType::Worker),
+        1 => Some(UnitType::Knight),
+        2 => Some(UnitType::Ranger),
+        3 => Some(UnitType::Mage),
+        4 => Some(UnitType::Healer),
+        5 => Some(UnitType::Factory),
+        6 => Some(UnitType::Rocket),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use comms::*;
+
+    #[test]
+    fn test_round_trip() {
+        let location = MapLocation::new(Planet::Mars, 49, 17);
+        let messages = vec![
+            Message::Rocket { location, arrival: 999 },
+            Message::Landing { location },
+            Message::Enemy { location, unit_type: UnitType::Mage },
+            Message::Karbonite { location, amount: 200 },
+            Message::Safe { location },
+        ];
+        for message in messages {
+            let value = encode(&message);
+            assert!(value > 0);
+            assert_eq!(decode(value), Some(message));
+        }
+    }
+
+    #[test]
+    fn test_empty() {
+        assert_eq!(decode(0), None);
+    }
+}
@@ -0,0 +1,219 @@
+use fnv::FnvHashMap;
+use std::collections::VecDeque;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+
+const WINDOW: usize = 10;
+const SMOOTHING: f64 = 0.3;
+const PASSIVE_INCOME: u32 = 10;
+const PASSIVE_DECAY: u32 = 40;
+const BASE_HARVEST: u32 = 3;
+const FACTORY_SPEND: u32 = 8;
+const MAX_FACTORIES: usize = 6;
+const MAX_WORKERS: usize = 16;
+const PAYBACK: u32 = 50;
+const LATE_ROCKETS: Rounds = 600;
+const ROCKET_SAVE: u32 = 20;
+
+type Karbonite = FnvHashMap<MapLocation, u32>;
+
+#[derive(Clone, Debug)]
+struct Deposit {
+    amount: u32,
+    rate: f64,
+}
+
+/// Tracks karbonite income and deposit depletion to forecast future income.
+#[derive(Debug)]
+pub struct Economy {
+    deposits: FnvHashMap<MapLocation, Deposit>,
+    /// What our workers took from each deposit since the last update.
+    mined: Karbonite,
+    history: VecDeque<u32>,
+    harvest: u32,
+    workers: usize,
+    stock: u32,
+}
+
+impl Economy {
+    pub fn new() -> Self {
+        Economy {
+            deposits: FnvHashMap::default(),
+            mined: FnvHashMap::default(),
+            history: VecDeque::new(),
+            harvest: BASE_HARVEST,
+            workers: 0,
+            stock: 0,
+        }
+    }
+
+    /// Folds in this turn's deposit amounts; call once per turn after updating `karbonite`.
+    pub fn update(&mut self, gc: &GameController, karbonite: &Karbonite, workers: usize) {
+        let level = gc.research_info().unwrap().get_level(&UnitType::Worker);
+        self.harvest = BASE_HARVEST + if level >= 1 { 1 } else { 0 };
+        self.workers = workers;
+        self.stock = gc.karbonite();
+        self.observe(karbonite, |location| gc.can_sense_location(*location));
+    }
+
+    /// Records karbonite a worker harvested from `location` this turn.
+    pub fn harvested(&mut self, location: MapLocation, amount: u32) {
+        *self.mined.entry(location).or_insert(0) += amount;
+    }
+
+    fn observe<F: Fn(&MapLocation) -> bool>(&mut self, karbonite: &Karbonite, visible: F) {
+        // Only depletion our own workers caused counts as income, so enemy
+        // harvesting and deposits we can no longer see don't inflate it
+        let mut harvested = 0;
+        for (location, deposit) in self.deposits.iter_mut() {
+            let now = karbonite.get(location).cloned().unwrap_or(0);
+            let mined = self.mined.get(location).cloned().unwrap_or(0);
+            let ours = if visible(location) { u32::min(mined, deposit.amount.saturating_sub(now)) } else { 0 };
+            harvested += ours;
+            deposit.rate = SMOOTHING * ours as f64 + (1.0 - SMOOTHING) * deposit.rate;
+            deposit.amount = now;
+        }
+        self.mined.clear();
+        self.deposits.retain(|_, deposit| deposit.amount > 0);
+        for (&location, &amount) in karbonite {
+            self.deposits.entry(location).or_insert(Deposit { amount, rate: 0.0 });
+        }
+
+        self.history.push_back(harvested + Self::passive(self.stock));
+        if self.history.len() > WINDOW {
+            self.history.pop_front();
+        }
+    }
+
+    /// Average income over the last few turns.
+    pub fn income(&self) -> u32 {
+        if self.history.len() == 0 { return Self::passive(self.stock) }
+        self.history.iter().sum::<u32>() / self.history.len() as u32
+    }
+
+    pub fn remaining(&self) -> u32 {
+        self.deposits.values().map(|deposit| deposit.amount).sum()
+    }
+
+    /// Expected karbonite gained over the next `rounds` rounds.
+    pub fn predict(&self, rounds: u32) -> u32 {
+        let capacity = self.workers as f64 * self.harvest as f64;
+        let observed = self.deposits.values().map(|deposit| deposit.rate).sum::<f64>();
+        let rate = f64::min(capacity, f64::max(observed, 0.0));
+
+        // Each deposit runs dry at its own pace
+        let share = if observed > 0.0 { rate / observed } else { 0.0 };
+        let harvested = self.deposits.values()
+            .map(|deposit| f64::min(deposit.amount as f64, deposit.rate * share * rounds as f64))
+            .sum::<f64>();
+        harvested as u32 + Self::passive(self.stock) * rounds
+    }
+
+    pub fn affordable(&self, cost: u32, rounds: u32) -> bool {
+        self.stock + self.predict(rounds) >= cost
+    }
+
+    /// Another worker pays for itself if there's enough unclaimed karbonite for it to harvest.
+    pub fn should_replicate(&self, workers: usize) -> bool {
+        let cost = UnitType::Worker.replicate_cost().unwrap();
+        let deposits = self.deposits.len();
+        let share = self.remaining() / (workers as u32 + 1);
+        workers < MAX_WORKERS
+        && workers < deposits
+        && share >= cost
+        && u32::min(share, self.harvest * PAYBACK) >= 2 * cost
+    }
+
+    /// Whether to save for another rocket: the first one right away, more late in
+    /// the game, or earlier when income beyond what the factories use can pay for one soon.
+    pub fn rocket_due(&self, round: Rounds, rockets: usize, factories: usize) -> bool {
+        let cost = UnitType::Rocket.blueprint_cost().unwrap();
+        rockets == 0
+        || round >= LATE_ROCKETS
+        || (self.income() > factories as u32 * FACTORY_SPEND && self.affordable(cost, ROCKET_SAVE))
+    }
+
+    /// Number of factories our income can keep busy.
+    pub fn factory_target(&self) -> usize {
+        let income = (self.income() + self.predict(PAYBACK) / PAYBACK) / 2;
+        let target = ((income + FACTORY_SPEND - 1) / FACTORY_SPEND) as usize;
+        usize::max(1, usize::min(target, MAX_FACTORIES))
+    }
+
+    fn passive(stock: u32) -> u32 {
+        PASSIVE_INCOME.saturating_sub(stock / PASSIVE_DECAY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use economy::*;
+
+    fn location(x: i32, y: i32) -> MapLocation {
+        MapLocation::new(Planet::Earth, x, y)
+    }
+
+    fn economy(workers: usize, stock: u32) -> Economy {
+        let mut economy = Economy::new();
+        economy.workers = workers;
+        economy.stock = stock;
+        economy
+    }
+
+    #[test]
+    fn test_income() {
+        let mut economy = economy(1, 400);
+        let mut karbonite = FnvHashMap::default();
+        karbonite.insert(location(0, 0), 20);
+        karbonite.insert(location(5, 5), 20);
+        economy.observe(&karbonite, |_| true);
+        assert_eq!(economy.income(), 0);
+
+        // Our worker took 3 from one deposit while the enemy took 4 from the other
+        economy.harvested(location(0, 0), 3);
+        karbonite.insert(location(0, 0), 17);
+        karbonite.insert(location(5, 5), 16);
+        economy.observe(&karbonite, |_| true);
+        assert_eq!(economy.income(), 1);
+
+        // Readings from deposits out of sight don't count either
+        economy.harvested(location(0, 0), 3);
+        karbonite.insert(location(0, 0), 14);
+        economy.observe(&karbonite, |_| false);
+        assert_eq!(economy.income(), 1);
+        assert_eq!(economy.remaining(), 30);
+    }
+
+    #[test]
+    fn test_predict() {
+        let mut economy = economy(2, 400);
+        let mut karbonite = FnvHashMap::default();
+        karbonite.insert(location(0, 0), 10);
+        karbonite.insert(location(5, 5), 100);
+        economy.observe(&karbonite, |_| true);
+        assert_eq!(economy.predict(10), 0);
+
+        for _ in 0..20 {
+            for (&location, amount) in karbonite.iter_mut() {
+                let taken = u32::min(*amount, 3);
+                economy.harvested(location, taken);
+                *amount -= taken;
+            }
+            karbonite.retain(|_, amount| *amount > 0);
+            economy.observe(&karbonite, |_| true);
+        }
+        assert_eq!(economy.remaining(), 40);
+
+        // The exhausted deposit no longer contributes, the other keeps its pace
+        let predicted = economy.predict(10);
+        assert_eq!(predicted, 29);
+        assert_eq!(economy.predict(100), 40);
+
+        // Passive income fills in when the stock is low
+        economy.stock = 0;
+        assert_eq!(economy.predict(10), predicted + 10 * PASSIVE_INCOME);
+    }
+}
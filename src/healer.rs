@@ -0,0 +1,229 @@
+use fnv::FnvHashSet;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use combat::*;
+
+// Worth of keeping a unit alive, in points of healing
+const SAVE: i32 = 1000;
+const STEP: i32 = 2;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// Damage the enemy could deal to `unit` next turn from where it stands.
+fn threat(gc: &GameController, unit: &Unit) -> i32 {
+    let unit_loc = loc(unit);
+    gc.sense_nearby_units_by_team(unit_loc, unit.vision_range(), unit.team().other()).iter()
+        .filter(|enemy| match enemy.unit_type() {
+            UnitType::Knight | UnitType::Ranger | UnitType::Mage => true,
+            _ => false,
+        })
+        .filter(|enemy| loc(enemy).distance_squared_to(unit_loc) <= enemy.attack_range().unwrap())
+        .map(|enemy| effective(enemy, unit))
+        .sum()
+}
+
+/// Heals from every ready healer, spread so as few allies as possible die next turn.
+pub fn heal(gc: &mut GameController, healers: &Vec<Unit>) {
+    let ready = healers.iter()
+        .filter(|healer| healer.location().is_on_map() && gc.is_heal_ready(healer.id()))
+        .collect::<Vec<_>>();
+
+    let mut seen = FnvHashSet::default();
+    let mut patients = Vec::new();
+    for healer in &ready {
+        for friend in gc.sense_nearby_units_by_team(loc(healer), healer.attack_range().unwrap(), healer.team()) {
+            if friend.unit_type().is_robot() && friend.health() < friend.max_health() && seen.insert(friend.id()) {
+                patients.push(friend);
+            }
+        }
+    }
+    if patients.len() == 0 { return }
+
+    let reach = ready.iter()
+        .map(|healer| patients.iter().map(|patient| gc.can_heal(healer.id(), patient.id())).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let amount = ready.iter().map(|healer| -healer.damage().unwrap()).collect::<Vec<_>>();
+    let health = patients.iter().map(|patient| patient.health() as i32).collect::<Vec<_>>();
+    let max = patients.iter().map(|patient| patient.max_health() as i32).collect::<Vec<_>>();
+    let danger = patients.iter().map(|patient| threat(gc, patient)).collect::<Vec<_>>();
+
+    for (h, patient) in distribute(&reach, &amount, &health, &max, &danger).into_iter().enumerate() {
+        if let Some(p) = patient {
+            gc.heal(ready[h].id(), patients[p].id());
+        }
+    }
+}
+
+/// Assigns each healer a patient index. Heals that lift a unit out of lethal
+/// danger come first, then heals on threatened units, then plain top-ups.
+pub fn distribute(reach: &Vec<Vec<bool>>, amount: &Vec<i32>, health: &Vec<i32>, max: &Vec<i32>,
+    danger: &Vec<i32>) -> Vec<Option<usize>> {
+
+    let mut health = health.clone();
+    let mut heals = vec![0; health.len()];
+    let mut result = vec![None; reach.len()];
+    loop {
+        let mut best = None;
+        for h in 0..reach.len() {
+            if result[h].is_some() { continue }
+            for p in 0..health.len() {
+                if !reach[h][p] { continue }
+                let healed = i32::min(amount[h], max[p] - health[p]);
+                if healed <= 0 { continue }
+                let saves = health[p] <= danger[p] && health[p] + healed > danger[p];
+                let benefit = if saves { SAVE } else { 0 } + if danger[p] > 0 { 2 * healed } else { healed };
+                // On ties spread heals out, then favour the lowest health
+                let score = (benefit, -heals[p], -health[p]);
+                best = match best {
+                    Some((_, _, top)) if top >= score => best,
+                    _ => Some((h, p, score)),
+                };
+            }
+        }
+        match best {
+            Some((h, p, _)) => {
+                result[h] = Some(p);
+                heals[p] += 1;
+                health[p] = i32::min(max[p], health[p] + amount[h]);
+            }
+            None => break,
+        }
+    }
+    result
+}
+
+/// Damage an extra turn of actions would let `unit` deal right now.
+fn extra_damage(gc: &GameController, unit: &Unit) -> i32 {
+    let unit_loc = loc(unit);
+    let enemies = gc.sense_nearby_units_by_team(unit_loc, unit.attack_range().unwrap(), unit.team().other());
+    let blind = if unit.unit_type() == UnitType::Ranger { unit.ranger_cannot_attack_range().unwrap() } else { 0 };
+    let hits = enemies.iter()
+        .filter(|enemy| loc(enemy).distance_squared_to(unit_loc) > blind)
+        .map(|enemy| i32::min(effective(unit, enemy), enemy.health() as i32));
+
+    match unit.unit_type() {
+        // Splash hits every enemy next to the target
+        UnitType::Mage => enemies.iter()
+            .map(|enemy| gc.sense_nearby_units_by_team(loc(enemy), 2, unit.team().other()).iter()
+                .map(|other| i32::min(effective(unit, other), other.health() as i32))
+                .sum::<i32>())
+            .max()
+            .unwrap_or(0),
+        // A fresh snipe is worth something even with nothing in range
+        UnitType::Ranger if unit.ability_heat().unwrap() >= 10 => {
+            i32::max(hits.max().unwrap_or(0), unit.damage().unwrap() / 2)
+        }
+        _ => hits.max().unwrap_or(0),
+    }
+}
+
+/// Overcharges the allies whose extra action does the most damage, one healer
+/// each, and returns them so they can act again.
+pub fn overcharge(gc: &mut GameController, healers: &Vec<Unit>) -> Vec<UnitID> {
+    let mut options = Vec::new();
+    for healer in healers {
+        if !healer.location().is_on_map() || !gc.is_overcharge_ready(healer.id()) { continue }
+        for friend in gc.sense_nearby_units_by_team(loc(healer), healer.ability_range().unwrap(), healer.team()) {
+            match friend.unit_type() {
+                UnitType::Knight | UnitType::Ranger | UnitType::Mage => {}
+                _ => continue,
+            }
+            // Only units that already spent their turn gain from a reset
+            if friend.attack_heat().unwrap() < 10 && friend.ability_heat().unwrap() < 10 { continue }
+            if !gc.can_overcharge(healer.id(), friend.id()) { continue }
+            let gain = extra_damage(gc, &friend);
+            if gain > 0 { options.push((gain, healer.id(), friend.id())) }
+        }
+    }
+    options.sort_by_key(|&(gain, _, _)| -gain);
+
+    let mut used = FnvHashSet::default();
+    let mut overcharged = Vec::new();
+    for (_, healer, friend) in options {
+        if used.contains(&healer) || overcharged.contains(&friend) { continue }
+        gc.overcharge(healer, friend);
+        used.insert(healer);
+        overcharged.push(friend);
+    }
+    overcharged
+}
+
+/// A nearby tile close to the allies that need healing (or, with none hurt,
+/// to the fighters at the front) that no enemy can reach next turn.
+/// `None` when no fighting allies are in sight.
+pub fn station(gc: &GameController, healer: &Unit) -> Option<MapLocation> {
+    let healer_loc = loc(healer);
+    let allies = gc.sense_nearby_units_by_team(healer_loc, healer.vision_range(), healer.team()).into_iter()
+        .filter(|ally| match ally.unit_type() {
+            UnitType::Knight | UnitType::Ranger | UnitType::Mage => true,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+    let damaged = allies.iter()
+        .filter(|ally| ally.health() < ally.max_health())
+        .collect::<Vec<_>>();
+    let followed = if damaged.len() > 0 { damaged } else { allies.iter().collect::<Vec<_>>() };
+    if followed.len() == 0 { return None }
+
+    // Weighted by missing health so the worst off pull hardest
+    let (mut x, mut y, mut total) = (0, 0, 0);
+    for ally in &followed {
+        let weight = (ally.max_health() - ally.health()) as i32 + 1;
+        x += loc(ally).x * weight;
+        y += loc(ally).y * weight;
+        total += weight;
+    }
+    let center = MapLocation::new(healer_loc.planet, x / total, y / total);
+
+    let enemies = gc.sense_nearby_units_by_team(healer_loc, healer.vision_range(), healer.team().other());
+    let mut best = None;
+    for dy in -STEP..STEP + 1 {
+        for dx in -STEP..STEP + 1 {
+            let tile = MapLocation::new(healer_loc.planet, healer_loc.x + dx, healer_loc.y + dy);
+            if !gc.can_sense_location(tile) { continue }
+            if tile != healer_loc && !gc.is_occupiable(tile).unwrap_or(false) { continue }
+
+            let danger = enemies.iter()
+                .filter(|enemy| loc(enemy).distance_squared_to(tile) <= reach(enemy))
+                .count() as i32;
+            let score = (-danger, -(tile.distance_squared_to(center) as i32));
+            best = match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((tile, score)),
+            };
+        }
+    }
+    best.map(|(tile, _)| tile)
+}
+
+#[cfg(test)]
+mod tests {
+    use healer::*;
+
+    #[test]
+    fn test_spread() {
+        // Two healers, two patients: both get healed instead of doubling up
+        let reach = vec![vec![true, true], vec![true, true]];
+        let result = distribute(&reach, &vec![10, 10], &vec![50, 60], &vec![100, 100], &vec![0, 0]);
+        assert!(result.contains(&Some(0)) && result.contains(&Some(1)));
+    }
+
+    #[test]
+    fn test_save() {
+        // The unit about to die gets healed over the one that's merely low
+        let reach = vec![vec![true, true]];
+        let result = distribute(&reach, &vec![10], &vec![20, 35], &vec![100, 100], &vec![0, 40]);
+        assert_eq!(result, vec![Some(1)]);
+    }
+
+    #[test]
+    fn test_full() {
+        let reach = vec![vec![true]];
+        let result = distribute(&reach, &vec![10], &vec![100], &vec![100], &vec![0]);
+        assert_eq!(result, vec![None]);
+    }
+}
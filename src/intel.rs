@@ -0,0 +1,75 @@
+use fnv::FnvHashMap;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+
+const STALE: Rounds = 50;
+
+/// Last known state of an enemy unit.
+#[derive(Clone, Debug)]
+pub struct Sighting {
+    pub id: UnitID,
+    pub unit_type: UnitType,
+    pub location: MapLocation,
+    pub health: u32,
+    pub round: Rounds,
+}
+
+/// Memory of every enemy unit we've seen, kept until we see it's gone.
+#[derive(Debug)]
+pub struct Intel {
+    sightings: FnvHashMap<UnitID, Sighting>,
+}
+
+impl Intel {
+    pub fn new() -> Self {
+        Intel { sightings: FnvHashMap::default() }
+    }
+
+    pub fn update(&mut self, gc: &GameController) {
+        let round = gc.round();
+        let enemy = gc.team().other();
+        let visible = gc.units().into_iter()
+            .filter(|unit| unit.team() == enemy)
+            .filter_map(|unit| {
+                unit.location().map_location().ok().map(|location| (unit, location))
+            })
+            .collect::<Vec<_>>();
+
+        // Forget units whose last known tile we can see but they aren't on
+        self.sightings.retain(|_, sighting| {
+            sighting.location.planet != gc.planet() || !gc.can_sense_location(sighting.location)
+        });
+
+        for (unit, location) in visible {
+            self.sightings.insert(unit.id(), Sighting {
+                id: unit.id(),
+                unit_type: unit.unit_type(),
+                location,
+                health: unit.health(),
+                round,
+            });
+        }
+    }
+
+    pub fn sightings(&self) -> Vec<&Sighting> {
+        self.sightings.values().collect::<Vec<_>>()
+    }
+
+    pub fn get(&self, id: UnitID) -> Option<&Sighting> {
+        self.sightings.get(&id)
+    }
+
+    /// Enemy robots seen recently, by type.
+    pub fn composition(&self, round: Rounds) -> FnvHashMap<UnitType, usize> {
+        let mut counts = FnvHashMap::default();
+        for sighting in self.sightings.values() {
+            if sighting.unit_type.is_robot() && sighting.round + STALE >= round {
+                *counts.entry(sighting.unit_type).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+}
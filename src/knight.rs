@@ -0,0 +1,93 @@
+use fnv::{FnvHashMap, FnvHashSet};
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use combat::*;
+use navigate::*;
+use solver::*;
+
+const JAVELIN_LEVEL: usize = 3;
+const INFEASIBLE: i32 = 1000;
+const SURROUND_MOVES: i32 = 8;
+const MOVE_WEIGHT: i32 = 2;
+const MAX_VALUE: i32 = 5;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// An adjacent tile from which a ready javelin reaches the most valuable enemy,
+/// when nothing worth throwing at is in reach already.
+pub fn javelin_tile(gc: &GameController, nav: &Navigator, knight: &Unit) -> Option<MapLocation> {
+    if gc.research_info().unwrap().get_level(&UnitType::Knight) < JAVELIN_LEVEL { return None }
+    if !gc.is_javelin_ready(knight.id()) || !gc.is_move_ready(knight.id()) { return None }
+
+    let knight_loc = loc(knight);
+    let range = knight.ability_range().unwrap();
+    let enemies = gc.sense_nearby_units_by_team(knight_loc, knight.vision_range(), knight.team().other());
+    let reachable = |tile: MapLocation| enemies.iter()
+        .filter(|enemy| loc(enemy).distance_squared_to(tile) <= range)
+        .map(|enemy| target_value(enemy.unit_type()))
+        .max()
+        .unwrap_or(0);
+
+    let here = reachable(knight_loc);
+    let mut best = None;
+    for dir in Direction::all() {
+        let tile = knight_loc.add(dir);
+        if tile == knight_loc || !gc.can_move(knight.id(), dir) || !nav.is_open(&tile) { continue }
+        let value = reachable(tile);
+        best = match best {
+            Some((_, top)) if top >= value => best,
+            _ => Some((tile, value)),
+        };
+    }
+
+    match best {
+        Some((tile, value)) if value > here => Some(tile),
+        _ => None,
+    }
+}
+
+/// Assigns engaging knights to distinct open tiles next to enemies, so several
+/// knights can hit one target at once instead of queueing behind each other.
+pub fn surround(gc: &GameController, nav: &mut Navigator, knights: &Vec<Unit>) -> FnvHashMap<UnitID, MapLocation> {
+    let mut posts = FnvHashMap::default();
+    let ours = knights.iter().map(|knight| knight.id()).collect::<FnvHashSet<_>>();
+
+    // Tiles next to each enemy, valued by the best enemy they touch
+    let mut tiles = FnvHashMap::default();
+    for knight in knights {
+        for enemy in gc.sense_nearby_units_by_team(loc(knight), knight.vision_range(), knight.team().other()) {
+            let enemy_loc = loc(&enemy);
+            for dir in Direction::all() {
+                let tile = enemy_loc.add(dir);
+                if tile == enemy_loc || !gc.can_sense_location(tile) { continue }
+                let open = gc.is_occupiable(tile).unwrap_or(false) || match gc.sense_unit_at_location(tile) {
+                    Ok(unit) => ours.contains(&unit.id()),
+                    Err(_) => false,
+                };
+                if !open { continue }
+                let value = tiles.entry(tile).or_insert(0);
+                *value = i32::max(*value, target_value(enemy.unit_type()));
+            }
+        }
+    }
+    if tiles.len() == 0 { return posts }
+    let tiles = tiles.into_iter().collect::<Vec<_>>();
+
+    let costs = knights.iter()
+        .map(|knight| tiles.iter().map(|&(tile, value)| {
+            let moves = nav.moves_between(&loc(knight), &tile);
+            if moves > SURROUND_MOVES { INFEASIBLE } else { moves * MOVE_WEIGHT + MAX_VALUE - value }
+        }).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    for (knight, tile) in hungarian(&costs).into_iter().enumerate() {
+        let tile = match tile { Some(tile) => tile, None => continue };
+        if costs[knight][tile] >= INFEASIBLE { continue }
+        posts.insert(knights[knight].id(), tiles[tile].0);
+    }
+    posts
+}
@@ -3,3 +3,21 @@ extern crate fnv;
 
 pub mod navigate;
 pub mod assign;
+pub mod comms;
+pub mod rocket;
+pub mod solver;
+pub mod task;
+pub mod placement;
+pub mod economy;
+pub mod budget;
+pub mod intel;
+pub mod production;
+pub mod research;
+pub mod combat;
+pub mod mage;
+pub mod ranger;
+pub mod knight;
+pub mod healer;
+pub mod retreat;
+pub mod squad;
+pub mod rally;
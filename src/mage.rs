@@ -0,0 +1,147 @@
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use combat::*;
+use navigate::*;
+
+const SPLASH: u32 = 2;
+const BLINK_LEVEL: usize = 4;
+const FRIENDLY_FIRE: i32 = 2;
+const FRONT_RADIUS: u32 = 100;
+const STEP: i32 = 2;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// Damage a shot at `target` does to enemies, less double the damage to friends
+/// (the mage included), with a bonus for each kill.
+fn splash_value(gc: &GameController, mage: &Unit, target: MapLocation) -> i32 {
+    gc.sense_nearby_units(target, SPLASH).iter()
+        .map(|unit| {
+            let health = unit.health() as i32;
+            let dealt = i32::min(effective(mage, unit), health);
+            if unit.team() == mage.team() {
+                -FRIENDLY_FIRE * dealt
+            } else if dealt >= health {
+                dealt + health
+            } else {
+                dealt
+            }
+        })
+        .sum()
+}
+
+/// Fires at the enemy whose splash does the most net damage, if any shot is worth it.
+pub fn splash(gc: &mut GameController, mage: &Unit) -> bool {
+    if !mage.location().is_on_map() || !gc.is_attack_ready(mage.id()) { return false }
+
+    let mut best = None;
+    for enemy in gc.sense_nearby_units_by_team(loc(mage), mage.attack_range().unwrap(), mage.team().other()) {
+        if !gc.can_attack(mage.id(), enemy.id()) { continue }
+        let value = splash_value(gc, mage, loc(&enemy));
+        best = match best {
+            Some((_, top)) if top >= value => best,
+            _ => Some((enemy.id(), value)),
+        };
+    }
+
+    match best {
+        Some((target, value)) if value > 0 => {
+            gc.attack(mage.id(), target);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Blinks a mage with its attack ready to the open tile that lets it splash
+/// the most, if that beats what it can hit from where it stands.
+pub fn blink(gc: &mut GameController, nav: &mut Navigator, mage: &Unit) -> bool {
+    if gc.research_info().unwrap().get_level(&UnitType::Mage) < BLINK_LEVEL { return false }
+    if !mage.location().is_on_map() || !gc.is_blink_ready(mage.id()) || !gc.is_attack_ready(mage.id()) { return false }
+
+    let mage_loc = loc(mage);
+    // Scoring only reads the world, so keep its borrow apart from the blink
+    let (best, here) = {
+        let range = mage.attack_range().unwrap();
+        let enemies = gc.sense_nearby_units_by_team(mage_loc, mage.vision_range(), mage.team().other());
+        let best_from = |tile: MapLocation| enemies.iter()
+            .filter(|enemy| loc(enemy).distance_squared_to(tile) <= range)
+            .map(|enemy| splash_value(gc, mage, loc(enemy)))
+            .max()
+            .unwrap_or(0);
+
+        let here = best_from(mage_loc);
+        let reach = mage.ability_range().unwrap() as i32;
+        let step = (reach as f64).sqrt() as i32;
+        let mut best = None;
+        for dy in -step..step + 1 {
+            for dx in -step..step + 1 {
+                if dx*dx + dy*dy > reach { continue }
+                let tile = MapLocation::new(mage_loc.planet, mage_loc.x + dx, mage_loc.y + dy);
+                if tile == mage_loc || !gc.can_blink(mage.id(), tile) || !nav.is_open(&tile) { continue }
+                let value = best_from(tile);
+                best = match best {
+                    Some((_, top)) if top >= value => best,
+                    _ => Some((tile, value)),
+                };
+            }
+        }
+        (best, here)
+    };
+
+    match best {
+        Some((tile, value)) if value > here => {
+            gc.blink(mage.id(), tile);
+            nav.teleport(mage.id(), &mage_loc, &tile);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// A nearby tile from which the mage can reach the closest enemy without
+/// standing ahead of our other fighters. `None` when no enemy is in sight.
+pub fn position(gc: &GameController, mage: &Unit) -> Option<MapLocation> {
+    let mage_loc = loc(mage);
+    let enemy = gc.sense_nearby_units_by_team(mage_loc, mage.vision_range(), mage.team().other())
+        .into_iter()
+        .min_by_key(|enemy| loc(enemy).distance_squared_to(mage_loc));
+    let enemy = match enemy { Some(enemy) => enemy, None => return None };
+    let en_loc = loc(&enemy);
+    let threat = if enemy.unit_type().is_robot() { enemy.attack_range().unwrap() } else { 0 };
+
+    // How close our front line already stands to the enemy
+    let front = gc.sense_nearby_units_by_team(en_loc, FRONT_RADIUS, mage.team()).into_iter()
+        .filter(|unit| match unit.unit_type() {
+            UnitType::Knight | UnitType::Ranger => true,
+            _ => false,
+        })
+        .map(|unit| loc(&unit).distance_squared_to(en_loc))
+        .min();
+
+    let range = mage.attack_range().unwrap();
+    let mut best = None;
+    for dy in -STEP..STEP + 1 {
+        for dx in -STEP..STEP + 1 {
+            let tile = MapLocation::new(mage_loc.planet, mage_loc.x + dx, mage_loc.y + dy);
+            if !gc.can_sense_location(tile) { continue }
+            if tile != mage_loc && !gc.is_occupiable(tile).unwrap_or(false) { continue }
+
+            let dist = tile.distance_squared_to(en_loc);
+            let behind = match front {
+                Some(front) => dist >= front,
+                None => dist > threat,
+            };
+            // In range, as far back as possible; otherwise as close as possible
+            let reach = if dist <= range { dist as i32 } else { -(dist as i32) };
+            let score = (behind, dist <= range, reach);
+            best = match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((tile, score)),
+            };
+        }
+    }
+    best.map(|(tile, _)| tile)
+}
@@ -1,7 +1,6 @@
 extern crate battlecode_engine as engine;
 extern crate battlecode as bc;
 extern crate fnv;
-extern crate rand;
 
 use engine::controller::*;
 use engine::world::*;
@@ -11,20 +10,32 @@ use engine::location::*;
 
 use Location::*;
 use Team::*;
-use Direction::*;
 use UnitType::*;
 
 use bc::navigate::*;
 use bc::assign::*;
+use bc::comms::*;
+use bc::rocket::*;
+use bc::solver::*;
+use bc::task::*;
+use bc::placement::*;
+use bc::economy::*;
+use bc::budget::*;
+use bc::intel::*;
+use bc::production::*;
+use bc::research::*;
+use bc::combat::*;
+use bc::mage::*;
+use bc::ranger::*;
+use bc::knight::*;
+use bc::healer::*;
+use bc::retreat::*;
+use bc::squad::*;
+use bc::rally::*;
 
 use fnv::FnvHashMap;
-use fnv::FnvHashSet;
 
-use rand::distributions::{IndependentSample, Range};
-use std::f64::consts::PI;
-use std::f64;
-
-const DIRECTIONS: [Direction;9] = [Center,North,Northeast,East,Southeast,South,Southwest,West,Northwest];
+const SWITCH_PENALTY: i64 = 3;
 
 fn loc(unit: &Unit) -> MapLocation {
     unit.location().map_location().unwrap()
@@ -45,9 +56,8 @@ fn main() {
         .get(0)
         .map(|unit| loc(unit));
 
-    let mut loc_num = 0;
-    let mut rally = starting_en_units
-        .get(loc_num)
+    let en_start = starting_en_units
+        .get(0)
         .map(|unit| loc(unit));
 
     let mut karb_locs = FnvHashMap::default();
@@ -63,52 +73,28 @@ fn main() {
         }
     }
 
-    let mut prod_num = 0;
-    let mut production_queue = Vec::new();
-    let mut switched = false;
-    if start != None && rally != None && nav.moves_between(&start.unwrap(), &rally.unwrap()) > min_possible_dist(&start.unwrap(),&rally.unwrap())*2 {
-        production_queue.push(Ranger);
-        production_queue.push(Ranger);
-        production_queue.push(Healer);
-        production_queue.push(Ranger);
-        production_queue.push(Ranger);
-        production_queue.push(Healer);
-
-        gc.queue_research(Worker);
-        gc.queue_research(Healer);
-        gc.queue_research(Healer);        
-        gc.queue_research(Ranger);
-        gc.queue_research(Rocket);
-        gc.queue_research(Healer);
-        gc.queue_research(Ranger);
-        gc.queue_research(Rocket);
-        switched = true;
-
-    }
-    else {
-        production_queue.push(Knight);
-        production_queue.push(Knight);
-        production_queue.push(Knight);
-        production_queue.push(Healer);
-        production_queue.push(Ranger);
-
-        // RESEARCH QUEUE
-        gc.queue_research(Knight);
-        gc.queue_research(Knight);
-        gc.queue_research(Healer);
-        gc.queue_research(Knight);
-        gc.queue_research(Rocket);
-        gc.queue_research(Healer);
-        gc.queue_research(Healer);
-        gc.queue_research(Rocket);
-        gc.queue_research(Ranger);
-        gc.queue_research(Ranger);
-        gc.queue_research(Mage);
-        gc.queue_research(Mage);
-    }
-
     let mut seen_locs = FnvHashMap::default();
 
+    let mut radio = Radio::new(&gc);
+    let mut safe_locs = Vec::new();
+    let mut mars_karb = FnvHashMap::default();
+    let mut incoming = FnvHashMap::default();
+    let mut auction = Auction::new(SWITCH_PENALTY);
+    let mut planner = Planner::new(&gc);
+    let mut economy = Economy::new();
+    let mut budget = Budget::new();
+    let mut scheduler = Scheduler::new(&gc, Launch::Staggered(10), RocketManifest::for_round(gc.round()));
+    let mut intel = Intel::new();
+    let mut production = Production::new(match (start, en_start) {
+        (Some(start), Some(en_start)) => nav.moves_between(&start, &en_start),
+        _ => i32::max_value(),
+    });
+    let mut research = Research::new();
+    let mut snipes = Snipes::new();
+    let mut wounded = Wounded::new();
+    let mut squads = Squads::new();
+    let mut rallies = Rally::new(&gc);
+
     loop {
         if gc.get_time_left_ms() < 1000 {
             println!("Time Left: {}", gc.get_time_left_ms());
@@ -116,18 +102,12 @@ fn main() {
         }
         nav.refresh(&gc);
 
-        let mut total_karb = 0;
-
         // Update Karb Map
         karb_locs.retain(|&loc,_| !(gc.can_sense_location(loc) && gc.karbonite_at(loc).unwrap() <= 0));
         karb_locs.iter_mut()
             .filter(|&(&loc, _)| gc.can_sense_location(loc))
             .for_each(|(&loc, karb)| *karb = gc.karbonite_at(loc).unwrap());
 
-        for (_,karb) in &karb_locs {
-            total_karb += karb;
-        }
-
         if gc.planet() == Planet::Mars {
             if gc.asteroid_pattern().has_asteroid(gc.round()) {
                 let asteroid_pattern = gc.asteroid_pattern();
@@ -136,43 +116,47 @@ fn main() {
             }
         }
 
-        seen_locs.iter_mut().for_each(|(_, time)| *time += 1);
+        // Communication
+        for message in radio.receive(&gc) {
+            match message {
+                Message::Safe { location } => if !safe_locs.contains(&location) { safe_locs.push(location) },
+                Message::Enemy { location, .. } => safe_locs.retain(|safe| safe.distance_squared_to(location) > 50),
+                Message::Karbonite { location, amount } => { mars_karb.insert(location, amount); },
+                Message::Rocket { location, arrival } => { incoming.insert(location, arrival); },
+                Message::Landing { location } => {
+                    // Planned but not launched: can't come down before a full flight from now
+                    let earliest = gc.round() + gc.orbit_pattern().duration(gc.round());
+                    incoming.entry(location).or_insert(earliest);
+                },
+            }
+        }
+        incoming.retain(|_, &mut arrival| arrival >= gc.round());
 
-        if gc.planet() == Planet::Earth {
-            if rally != None && gc.has_unit_at_location(rally.unwrap()) && gc.sense_unit_at_location(rally.unwrap()).unwrap().team() == gc.team() && gc.sense_unit_at_location(rally.unwrap()).unwrap().unit_type() != Worker {
-                loc_num = (loc_num +1);
-                if loc_num < starting_en_units.len() {
-                    rally = starting_en_units.get(loc_num).map(|unit| loc(unit));
-                }
-                else {
-                    let x_range = Range::new(0, starting_map.width);
-                    let y_range = Range::new(0, starting_map.height);
-                    let mut rng = rand::thread_rng();
-                    let x = x_range.ind_sample(&mut rng);
-                    let y = y_range.ind_sample(&mut rng);
-
-                    let loc = MapLocation::new(gc.planet(),x as i32,y as i32);
-                    if starting_map.is_passable_terrain_at(loc).unwrap() {
-                        rally = Some(loc);
-                    }
+        if gc.planet() == Planet::Mars {
+            let enemies = gc.units().into_iter()
+                .filter(|unit| unit.team() != gc.team() && unit.location().is_on_map())
+                .collect::<Vec<_>>();
+            for enemy in enemies.iter().take(20) {
+                radio.send(Message::Enemy { location: loc(enemy), unit_type: enemy.unit_type() });
+            }
+            if gc.round() % 25 == 0 {
+                for (&location, &amount) in karb_locs.iter().take(20) {
+                    radio.send(Message::Karbonite { location, amount });
                 }
             }
-        }
-        else {
-            if rally == None || gc.has_unit_at_location(rally.unwrap()) && gc.sense_unit_at_location(rally.unwrap()).unwrap().team() == gc.team() && gc.sense_unit_at_location(rally.unwrap()).unwrap().unit_type() != Worker{
-                let x_range = Range::new(0, gc.starting_map(Planet::Mars).width);
-                let y_range = Range::new(0, gc.starting_map(Planet::Mars).height);
-                let mut rng = rand::thread_rng();
-                let x = x_range.ind_sample(&mut rng);
-                let y = y_range.ind_sample(&mut rng);
-
-                let loc = MapLocation::new(Planet::Mars,x as i32,y as i32);
-                if starting_map.is_passable_terrain_at(loc).unwrap() {
-                    rally = Some(loc);
+            for unit in gc.my_units().iter().filter(|unit| unit.location().is_on_map()).take(10) {
+                let location = loc(unit);
+                if enemies.iter().all(|en| loc(en).distance_squared_to(location) > 50) {
+                    if let Some(location) = landing_tile(&gc, &starting_map, &location) {
+                        radio.send(Message::Safe { location });
+                    }
                 }
             }
         }
 
+        intel.update(&gc);
+        seen_locs.iter_mut().for_each(|(_, time)| *time += 1);
+
         for x in 0..starting_map.width {
             for y in 0..starting_map.height {
                 let loc = MapLocation::new(gc.planet(),x as i32,y as i32);
@@ -195,23 +179,59 @@ fn main() {
         let mages = get_type(&gc, Mage);
         let healers = get_type(&gc, Healer);
 
-        if !switched && rangers.len() > 6 {
-            production_queue.clear();
-            production_queue.push(Ranger);
-            production_queue.push(Ranger);
-            production_queue.push(Healer);
-            production_queue.push(Knight);
-            switched = true;
+        economy.update(&gc, &karb_locs, workers.len());
+
+        // Pick blueprint sites up front so karbonite is only held for work that can happen
+        let rocket_researched = gc.research_info().unwrap().get_level(&Rocket) > 0;
+        let factory_wanted = fin_facts.len() + un_facts.len() < economy.factory_target();
+        let factory_site = if factory_wanted {
+            planner.choose(&gc, &mut nav, Factory, &workers, &karb_locs, en_start)
+        } else {
+            None
+        };
+        let rocket_site = if rocket_researched {
+            planner.choose(&gc, &mut nav, Rocket, &workers, &karb_locs, en_start)
+        } else {
+            None
+        };
+        let replicate_wanted = fin_facts.len() + un_facts.len() != 0
+            && economy.should_replicate(workers.len())
+            && workers.iter().any(|worker| Direction::all().into_iter().any(|d| gc.can_replicate(worker.id(), d)));
+
+        // Reserve karbonite before anything spends it in loop order; Mars builds nothing
+        budget.plan(gc.karbonite());
+        if gc.planet() == Planet::Earth {
+            if rocket_site.is_some() && economy.rocket_due(gc.round(), un_rockets.len() + fin_rockets.len(), fin_facts.len()) {
+                budget.reserve(Purpose::Rocket, Rocket.blueprint_cost().unwrap());
+            }
+            if factory_site.is_some() {
+                budget.reserve(Purpose::Blueprint, Factory.blueprint_cost().unwrap());
+            }
+        }
+        if replicate_wanted {
+            budget.reserve(Purpose::Replicate, Worker.replicate_cost().unwrap());
+        }
+
+        let mut army = FnvHashMap::default();
+        army.insert(Knight, knights.len());
+        army.insert(Ranger, rangers.len());
+        army.insert(Mage, mages.len());
+        army.insert(Healer, healers.len());
+        production.plan(&gc, &intel, &army);
+        // Research is shared, so only Earth manages the queue
+        if gc.planet() == Planet::Earth {
+            research.update(&mut gc, &production, &intel);
         }
 
         // FACTORY
         for fact in &fin_facts {
             if workers.len() == 0 {
-                try_produce(&mut gc,fact,Worker);
+                try_produce(&mut gc, &mut budget, fact, Worker);
             }
-            else if !(gc.research_info().unwrap().get_level(&Rocket) > 0 && un_rockets.len() + fin_rockets.len() ==0) {
-                if try_produce(&mut gc, fact, production_queue[prod_num%production_queue.len()]) {
-                    prod_num = (prod_num+1)%production_queue.len();
+            else {
+                let unit_type = production.next();
+                if try_produce(&mut gc, &mut budget, fact, unit_type) {
+                    production.produced(unit_type);
                 }
             }
             try_unload(&mut gc,fact)
@@ -220,182 +240,237 @@ fn main() {
         let workers = get_type(&gc, Worker);
 
         // WORKER
+        let mut replicated = 0;
         for worker in &workers {
-            if (fin_facts.len() + un_facts.len() != 0
-            && !(gc.research_info().unwrap().get_level(&Rocket) > 0
-            && un_rockets.len() + fin_rockets.len() ==0)
-            && (workers.len() as u32) < u32::min(total_karb/150,8)) || gc.round() > 750 {
-                try_replicate(&mut gc, &worker);
+            // Recount as we go so one turn can't overshoot the forecast
+            let wanted = replicate_wanted && economy.should_replicate(workers.len() + replicated);
+            if (wanted || gc.round() > 750) && try_replicate(&mut gc, &mut budget, &worker) {
+                replicated += 1;
             }
         }
 
         let workers = get_type(&gc, Worker);
 
+        // BOARDING
+        let boarding = if gc.planet() == Planet::Earth {
+            let mut available = FnvHashMap::default();
+            for unit in gc.my_units() {
+                *available.entry(unit.unit_type()).or_insert(0) += 1;
+            }
+            scheduler.set_manifest(RocketManifest::for_round(gc.round()).relaxed(&available));
+            assign_rockets(&mut nav, &gc, scheduler.manifest(), &fin_rockets, &workers, &knights, &rangers, &mages, &healers)
+        } else {
+            FnvHashMap::default()
+        };
+
+        let mut blueprints = Vec::new();
+        if let Some(site) = factory_site {
+            if budget.can_spend(Purpose::Blueprint, Factory.blueprint_cost().unwrap()) {
+                blueprints.push((Factory, site));
+            }
+        }
+        if let Some(site) = rocket_site {
+            if budget.can_spend(Purpose::Rocket, Rocket.blueprint_cost().unwrap()) {
+                blueprints.push((Rocket, site));
+            }
+        }
+
+        let tasks = assign_workers(&gc, &mut nav, &mut auction, &workers, &karb_locs,
+            &un_facts, &fin_facts, &un_rockets, &blueprints, &boarding);
         for worker in &workers {
-            try_build(&mut gc, worker)
-            || (fin_facts.len() + un_facts.len() < 6 && try_blueprint(&mut gc, &nav, worker,Factory))
-            || (gc.research_info().unwrap().get_level(&Rocket)>0 && try_blueprint(&mut gc, &nav, worker,Rocket))
-            || try_harvest(&mut gc, worker)
-            || try_repair(&mut gc, worker);
+            if let Some(task) = tasks.get(&worker.id()) {
+                if task.perform(&mut gc, worker) {
+                    charge(&mut budget, task);
+                    tally(&mut economy, worker, task);
+                }
+            }
+        }
+
+        // RALLY
+        let home = fin_facts.get(0).map(|fact| loc(fact)).or(start);
+        let landings = incoming.keys().cloned().collect::<Vec<_>>();
+        rallies.plan(&gc, &intel, &seen_locs, home, &landings);
+        let rally = rallies.attack();
+
+        // SQUADS
+        let members = knights.iter().chain(rangers.iter()).chain(mages.iter()).chain(healers.iter()).cloned().collect::<Vec<_>>();
+        squads.update(&gc, &members, &boarding, un_rockets.len() + fin_rockets.len() > 0);
+        squads.order(Role::Attack, rally);
+        squads.order(Role::Defend, rallies.defend());
+        squads.order(Role::Escort, un_rockets.iter().chain(fin_rockets.iter()).map(|rocket| loc(rocket)).nth(0));
+        squads.order(Role::Scout, rallies.scout());
+
+        // ATTACK
+        let attackers = knights.iter().chain(rangers.iter()).cloned().collect::<Vec<_>>();
+        fire(&mut gc, &attackers);
+        let sniping = snipes.plan(&mut gc, &intel, &rangers);
+        let fighters = knights.iter().chain(rangers.iter()).chain(mages.iter()).cloned().collect::<Vec<_>>();
+        wounded.update(&fighters, &healers);
+        for unit in &fighters {
+            if boarding.contains_key(&unit.id()) || sniping.contains(&unit.id()) || !wounded.is_retreating(unit.id()) { continue }
+            if let Some(refuge) = wounded.refuge(&mut nav, unit, &healers, &fin_facts) {
+                try_move_to(&mut nav, unit, &refuge);
+            }
         }
-        assign_workers(&mut nav, &workers, &karb_locs, &un_facts, &fin_facts, &un_rockets);
 
         // KNIGHT
+        let stances = knights.iter()
+            .map(|knight| (knight.id(), evaluate(&gc, loc(knight), 64)))
+            .collect::<FnvHashMap<_, _>>();
+        let engaging = knights.iter()
+            .filter(|knight| stances[&knight.id()] == Stance::Engage)
+            .filter(|knight| !boarding.contains_key(&knight.id()) && !wounded.is_retreating(knight.id()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let posts = surround(&gc, &mut nav, &engaging);
         for knight in &knights {
-            try_attack(&mut gc, &mut nav, knight);
             try_javelin(&mut gc, &mut nav, knight);
+            if boarding.contains_key(&knight.id()) || wounded.is_retreating(knight.id()) { continue }
 
             let knight_loc = loc(knight);
 
             let mut nearby_units = gc.sense_nearby_units_by_team(knight_loc, 64, gc.team().other());
             nearby_units.sort_by_key(|en| nav.moves_between(&knight_loc, &loc(en)));
             if nearby_units.len() != 0 {
-                let friends = gc.sense_nearby_units_by_team(knight_loc, 9, gc.team());
-                let mut enemies = gc.sense_nearby_units_by_team(knight_loc, 64, gc.team().other());
-                enemies.retain(|en| en.unit_type().is_robot() && en.unit_type() != Worker && en.unit_type() != Healer);
-                enemies.retain(|en| loc(en).distance_squared_to(knight_loc) <= en.attack_range().unwrap());
-
-                if friends.len() >= enemies.len() || (enemies.len() != 0 && nav.moves_between(&knight_loc, &loc(&nearby_units[0])) <= 3) {
-                    try_move_to(&mut nav, knight, &loc(&nearby_units[0]));
-                }
-                else {
-                    let my_loc = knight_loc;
-                    let en_loc = loc(&nearby_units[0]);
-                    if start != None {
-                        try_move_to(&mut nav, knight, &start.unwrap());
+                let stance = stances[&knight.id()];
+                let setup = if stance == Stance::Retreat { None } else { javelin_tile(&gc, &nav, knight) };
+                match (stance, setup) {
+                    (_, Some(tile)) => { try_move_to(&mut nav, knight, &tile); }
+                    (Stance::Engage, None) => {
+                        let post = posts.get(&knight.id()).cloned().unwrap_or(loc(&nearby_units[0]));
+                        try_move_to(&mut nav, knight, &post);
                     }
+                    (Stance::Hold, None) => { try_move_to(&mut nav, knight, &knight_loc); }
+                    (Stance::Retreat, None) => if start != None { try_move_to(&mut nav, knight, &start.unwrap()); },
                 }
-            } else if rally != None {
+            } else if !squads.advance(&mut nav, knight) && rally != None {
                 try_move_to(&mut nav, knight, &rally.unwrap());
             }
         }
 
         // RANGER
         for ranger in &rangers {
-            try_attack(&mut gc, &mut nav, ranger);
+            if boarding.contains_key(&ranger.id()) || wounded.is_retreating(ranger.id()) { continue }
+            if sniping.contains(&ranger.id()) || ranger.ranger_is_sniping().unwrap() { continue }
 
             let ranger_loc = loc(ranger);
 
-            let mut nearby_units = gc.sense_nearby_units_by_team(ranger_loc, 50, gc.team().other());
+            let mut nearby_units = gc.sense_nearby_units_by_team(ranger_loc, ranger.vision_range(), gc.team().other());
             nearby_units.sort_by_key(|en| nav.moves_between(&ranger_loc, &loc(en)));
             if nearby_units.len() != 0 {
-                let friends = gc.sense_nearby_units_by_team(ranger_loc, 25, gc.team());
-                let mut enemies = gc.sense_nearby_units_by_team(ranger_loc, 50, gc.team().other());
-                enemies.retain(|en| en.unit_type().is_robot() && en.unit_type() != Worker && en.unit_type() != Healer);
-                enemies.retain(|en| loc(en).distance_squared_to(ranger_loc) < en.attack_range().unwrap());
-                if friends.len() < enemies.len() || enemies.len() != 0 && nav.moves_between(&ranger_loc, &loc(&enemies[0])) <= 6 {
-                    let my_loc = ranger_loc;
-                    let en_loc = loc(&nearby_units[0]);
+                if evaluate(&gc, ranger_loc, 50) == Stance::Retreat {
                     if start != None {
                         try_move_to(&mut nav, ranger, &start.unwrap());
                     }
                 }
                 else {
-                    try_move_to(&mut nav, ranger, &ranger_loc);
+                    let tile = kite(&gc, ranger).unwrap_or(ranger_loc);
+                    try_move_to(&mut nav, ranger, &tile);
                 }
             }
-            else if rally != None && try_move_to(&mut nav, ranger, &rally.unwrap()) {
-
+            else if !squads.advance(&mut nav, ranger) && rally != None {
+                try_move_to(&mut nav, ranger, &rally.unwrap());
             }
         }
 
-        let mut overcharged_units = Vec::new();
+        // MAGE
+        for mage in &mages {
+            splash(&mut gc, mage);
+            if boarding.contains_key(&mage.id()) || wounded.is_retreating(mage.id()) { continue }
+            if blink(&mut gc, &mut nav, mage) {
+                if let Ok(mage) = gc.unit(mage.id()) { splash(&mut gc, &mage); }
+                continue
+            }
+
+            match position(&gc, mage) {
+                Some(tile) => { try_move_to(&mut nav, mage, &tile); }
+                None => if !squads.advance(&mut nav, mage) && rally != None { try_move_to(&mut nav, mage, &rally.unwrap()); },
+            }
+        }
 
         // Healer
+        heal(&mut gc, &healers);
+        let overcharged_units = overcharge(&mut gc, &healers);
         for healer in &healers {
+            if boarding.contains_key(&healer.id()) { continue }
 
-            try_heal(&mut gc, &mut nav, healer);
-            let healer_loc = loc(healer);
-
-            if let Some(overcharged) = try_overcharge(&mut gc, &mut nav, healer) {
-                overcharged_units.push(overcharged);
-            }
-
-            let mut nearby_units = gc.sense_nearby_units_by_team(healer_loc, 50, gc.team().other());
-            nearby_units.sort_by_key(|en| nav.moves_between(&healer_loc, &loc(en)));
-            if nearby_units.len() != 0 {
-                let friends = gc.sense_nearby_units_by_team(healer_loc, 25, gc.team());
-                let mut enemies = gc.sense_nearby_units_by_team((healer_loc), 50, gc.team().other());
-                enemies.retain(|en| en.unit_type().is_robot() && en.unit_type() != Worker && en.unit_type() != Healer);
-                enemies.retain(|en| loc(en).distance_squared_to(healer_loc) < en.attack_range().unwrap());
-                if friends.len() < enemies.len() || enemies.len() != 0 && nav.moves_between(&healer_loc, &loc(&enemies[0])) <= 5 {
-                    let my_loc = healer_loc;
-                    let en_loc = loc(&nearby_units[0]);
-                    if start != None {
-                        try_move_to(&mut nav, healer, &start.unwrap());
-                    }
-                }
-                else {
-                    try_move_to(&mut nav, healer, &healer_loc);
-                }
-            }
-            else if rally != None {
-                try_move_to(&mut nav, healer, &rally.unwrap());
+            match station(&gc, healer) {
+                Some(tile) => { try_move_to(&mut nav, healer, &tile); }
+                None => if !squads.advance(&mut nav, healer) && rally != None { try_move_to(&mut nav, healer, &rally.unwrap()); },
             }
         }
 
         for unit_id in overcharged_units {
             let unit = gc.unit(unit_id).unwrap();
-            try_attack(&mut gc, &mut nav, &unit) || try_javelin(&mut gc, &mut nav, &unit);
+            match unit.unit_type() {
+                Mage => { splash(&mut gc, &unit); }
+                Ranger => if fire(&mut gc, &vec![unit.clone()]).len() == 0 { snipes.plan(&mut gc, &intel, &vec![unit.clone()]); },
+                _ => if fire(&mut gc, &vec![unit.clone()]).len() == 0 { try_javelin(&mut gc, &mut nav, &unit); },
+            }
         }
 
+        nav.execute(&mut gc);
         if gc.planet() == Planet::Earth {
-            let boarding = assign_rockets(&mut nav, &gc, &fin_rockets, &workers, &knights, &rangers, &healers);
-            nav.execute(&mut gc);
-            for rocket in &fin_rockets { try_load(&mut gc, rocket, &boarding); }
-        } else {
-            nav.execute(&mut gc);
+            for rocket in &fin_rockets { try_load(&mut gc, rocket, scheduler.manifest(), &boarding); }
+        }
+
+        for (&id, task) in &tasks {
+            if let Ok(worker) = gc.unit(id) {
+                if worker.location().is_on_map() && task.perform(&mut gc, &worker) {
+                    charge(&mut budget, task);
+                    tally(&mut economy, &worker, task);
+                }
+            }
         }
 
-        for knight in &knights { try_attack(&mut gc, &mut nav, knight); try_javelin(&mut gc, &mut nav, knight); }
-        for ranger in &rangers { try_attack(&mut gc, &mut nav, ranger); }
-        for healer in &healers { try_heal(&mut gc, &mut nav, healer); }
+        let attackers = attackers.iter().filter_map(|unit| gc.unit(unit.id()).ok()).collect::<Vec<_>>();
+        fire(&mut gc, &attackers);
+        for knight in &knights { try_javelin(&mut gc, &mut nav, knight); }
+        for mage in &mages {
+            if let Ok(mage) = gc.unit(mage.id()) { splash(&mut gc, &mage); }
+        }
+        let healers = healers.iter().filter_map(|healer| gc.unit(healer.id()).ok()).collect::<Vec<_>>();
+        heal(&mut gc, &healers);
 
         // ROCKET
         let (fin_rockets,_):(Vec<_>,Vec<_>) = get_type(&gc,Rocket)
             .into_iter().partition(|rocket| rocket.structure_is_built().unwrap());
-        for rocket in &fin_rockets {
-            if !rocket.rocket_is_used().unwrap() {
-                let period = gc.orbit_pattern().period;
-                let amplitude = gc.orbit_pattern().amplitude;
-                let velocity = amplitude as f64 *2.0 as f64 *PI/period as f64 * (gc.round() as f64 *2.0 as f64 *PI/period as f64).cos();
-
-                if (rocket.structure_garrison().unwrap().len() >= 8 && velocity < 1.0) || (rocket.health() != rocket.max_health() && rocket.structure_garrison().unwrap().len() > 0) {
-                    let x_range = Range::new(0, gc.starting_map(Planet::Mars).width);
-                    let y_range = Range::new(0, gc.starting_map(Planet::Mars).height);
-                    let mut rng = rand::thread_rng();
-                    let x = x_range.ind_sample(&mut rng);
-                    let y = y_range.ind_sample(&mut rng);
-
-                    let loc = MapLocation::new(Planet::Mars,x as i32,y as i32);
-                    if gc.can_launch_rocket(rocket.id(),loc) {
-                        gc.launch_rocket(rocket.id(),loc);
-                    }
+        if gc.planet() == Planet::Earth {
+            if gc.round() > 650 {
+                scheduler.set_mode(Launch::Synchronized);
+            }
+            let preferred = safe_locs.iter()
+                .chain(mars_karb.keys())
+                .cloned()
+                .collect::<Vec<_>>();
+            for location in scheduler.plan(&fin_rockets, &preferred) {
+                radio.send(Message::Landing { location });
+            }
+            for (id, loc) in scheduler.launches(&gc, &fin_rockets, &boarding) {
+                if gc.can_launch_rocket(id,loc) {
+                    let arrival = gc.round() + gc.orbit_pattern().duration(gc.round());
+                    gc.launch_rocket(id,loc);
+                    scheduler.launched(gc.round(), id);
+                    safe_locs.retain(|&safe| safe != loc);
+                    mars_karb.remove(&loc);
+                    radio.send(Message::Rocket { location: loc, arrival });
                 }
             }
-            else {
+        }
+        for rocket in &fin_rockets {
+            if rocket.rocket_is_used().unwrap() {
                 if rocket.structure_garrison().unwrap().len() > 0 {
                     try_unload(&mut gc, rocket);
                 }
             }
         }
 
+        radio.flush(&mut gc);
         gc.next_turn();
 
     }
 }
 
-fn min_possible_dist(loc1: &MapLocation, loc2: &MapLocation) -> i32{
-    let dx = (loc1.x - loc2.x).abs();
-    let dy = (loc1.y - loc2.y).abs();
-    if dx > dy {
-        return dx
-    }
-    else {
-        return dy 
-    }
-}
-
 fn get_type(gc: &GameController, unit_type: UnitType) -> Vec<Unit> {
     gc.my_units().into_iter()
         .filter(|unit| unit.unit_type() == unit_type)
@@ -404,58 +479,44 @@ fn get_type(gc: &GameController, unit_type: UnitType) -> Vec<Unit> {
 }
 
 // WORKER METHODS
-fn try_harvest(gc: &mut GameController, unit: &Unit) -> bool {
-    for &d in &DIRECTIONS {
-        if gc.can_harvest(unit.id(),d) {
-            gc.harvest(unit.id(),d);
-            return true
+/// A passable tile near `near` with no unit on or next to it, since a landing
+/// rocket destroys everything around where it comes down.
+fn landing_tile(gc: &GameController, map: &PlanetMap, near: &MapLocation) -> Option<MapLocation> {
+    for dy in -2..3 {
+        for dx in -2..3 {
+            let tile = MapLocation::new(near.planet, near.x + dx, near.y + dy);
+            if !gc.can_sense_location(tile) || !map.is_passable_terrain_at(tile).unwrap_or(false) { continue }
+            if gc.sense_nearby_units(tile, 2).len() == 0 { return Some(tile) }
         }
     }
-    return false
+    None
 }
 
-fn try_replicate(gc: &mut GameController, unit: &Unit) -> bool {
+fn try_replicate(gc: &mut GameController, budget: &mut Budget, unit: &Unit) -> bool {
+    let cost = Worker.replicate_cost().unwrap();
+    if !budget.can_spend(Purpose::Replicate, cost) { return false }
     for d in Direction::all() {
         if gc.can_replicate(unit.id(),d) {
             gc.replicate(unit.id(),d);
+            budget.spend(Purpose::Replicate, cost);
             return true
         }
     }
     return false
 }
 
-fn try_blueprint(gc: &mut GameController, nav: &Navigator, unit: &Unit, building_type: UnitType) -> bool {
-    let location = loc(unit);
-    for d in Direction::all() {
-        if gc.can_blueprint(unit.id(),building_type,d)
-        && nav.neighbors(&location.add(d)) > 4 {
-            gc.blueprint(unit.id(),building_type,d);
-            return true
-        }
+fn charge(budget: &mut Budget, task: &Task) {
+    match *task {
+        Task::Blueprint(Rocket, _) => budget.spend(Purpose::Rocket, Rocket.blueprint_cost().unwrap()),
+        Task::Blueprint(building_type, _) => budget.spend(Purpose::Blueprint, building_type.blueprint_cost().unwrap()),
+        _ => (),
     }
-    return false
 }
 
-fn try_build(gc: &mut GameController, unit: &Unit) -> bool {
-    let units = gc.sense_nearby_units(loc(unit),2);
-    for building in units {
-        if gc.can_build(unit.id(),building.id()) {
-            gc.build(unit.id(),building.id());
-            return true
-        }
+fn tally(economy: &mut Economy, worker: &Unit, task: &Task) {
+    if let Task::Harvest(location) = *task {
+        economy.harvested(location, worker.worker_harvest_amount().unwrap());
     }
-    return false
-}
-
-fn try_repair(gc: &mut GameController, unit: &Unit) -> bool {
-    let units = gc.sense_nearby_units(loc(unit),2);
-    for building in units {
-        if gc.can_repair(unit.id(),building.id()) {
-            gc.repair(unit.id(),building.id());
-            return true
-        }
-    }
-    return false
 }
 
 fn try_move_to(nav: &mut Navigator, unit: &Unit, loc: &MapLocation) -> bool {
@@ -464,9 +525,11 @@ fn try_move_to(nav: &mut Navigator, unit: &Unit, loc: &MapLocation) -> bool {
 }
 
 // FACTORY METHODS
-fn try_produce(gc: &mut GameController, fact: &Unit, unit_type: UnitType) -> bool {
-    if gc.can_produce_robot(fact.id(),unit_type) {
+fn try_produce(gc: &mut GameController, budget: &mut Budget, fact: &Unit, unit_type: UnitType) -> bool {
+    let cost = unit_type.factory_cost().unwrap();
+    if budget.can_spend(Purpose::Produce, cost) && gc.can_produce_robot(fact.id(),unit_type) {
         gc.produce_robot(fact.id(),unit_type);
+        budget.spend(Purpose::Produce, cost);
         return true
     }
     return false
@@ -483,78 +546,20 @@ fn try_unload(gc: &mut GameController, building: &Unit) {
 }
 
 // ROCKET METHODS
-fn try_load(gc: &mut GameController, rocket: &Unit, boarding: &FnvHashSet<u16>) {
+fn try_load(gc: &mut GameController, rocket: &Unit, manifest: &RocketManifest, boarding: &FnvHashMap<UnitID, MapLocation>) {
+    let capacity = rocket.structure_max_capacity().unwrap();
+    let mut garrison = garrison_types(gc, rocket);
     for unit in gc.sense_nearby_units_by_team(loc(rocket), 2, gc.team()) {
-        if boarding.contains(&unit.id())
+        if boarding.contains_key(&unit.id())
+        && manifest.accepts(&garrison, capacity, unit.unit_type())
         && gc.can_load(rocket.id(),unit.id()) {
             gc.load(rocket.id(),unit.id());
+            garrison.push(unit.unit_type());
         }
     }
 }
 
 // ARMY METHODS
-fn try_attack(gc: &mut GameController, nav: &mut Navigator, unit: &Unit) -> bool {
-    let mut en_units = gc.sense_nearby_units_by_team(loc(unit) ,unit.attack_range().unwrap(),unit.team().other());
-    let (mut worker, mut other):(Vec<_>,Vec<_>) = en_units.into_iter().partition(|en| en.unit_type() == Worker);
-    worker.sort_by_key(|en| en.health());
-    other.sort_by_key(|en| en.health());
-    if gc.is_attack_ready(unit.id()) {
-        for enemy in other {
-            if gc.can_attack(unit.id(),enemy.id()) {
-                gc.attack(unit.id(),enemy.id());
-                return true
-            }
-        }
-        for enemy in worker {
-            if gc.can_attack(unit.id(),enemy.id()) {
-                gc.attack(unit.id(),enemy.id());
-                return true
-            }
-        }
-    }
-    return false
-}
-
-fn try_heal(gc: &mut GameController, nav: &mut Navigator, healer: &Unit) -> bool {
-    let mut units = gc.sense_nearby_units_by_team(loc(healer), healer.attack_range().unwrap(),healer.team());
-    let (mut worker, mut other):(Vec<_>,Vec<_>) = units.into_iter().partition(|en| en.unit_type() == Worker);
-    worker.sort_by_key(|unit| unit.health());
-    other.sort_by_key(|unit| unit.health());
-    if gc.is_heal_ready(healer.id()) {
-        for friend in other {
-            if friend.health() != friend.max_health() && gc.can_heal(healer.id(),friend.id()) {
-                gc.heal(healer.id(),friend.id());
-                return true;
-            }
-        }
-        for friend in worker {
-            if friend.health() != friend.max_health() && gc.can_heal(healer.id(),friend.id()) {
-                gc.heal(healer.id(),friend.id());
-                return true;
-            }
-        }
-    }
-    return false
-}
-
-fn try_overcharge(gc: &mut GameController, nav: &mut Navigator, healer: &Unit) -> Option<UnitID> {
-    let mut units = gc.sense_nearby_units_by_team(loc(healer), healer.ability_range().unwrap(),healer.team());
-    units.sort_by_key(|en| -nav.moves_between(&loc(healer),&loc(en)));
-    if gc.is_overcharge_ready(healer.id()) {
-        for friend in units {
-            if !friend.unit_type().is_robot() || friend.unit_type() == Worker {
-                continue
-            }
-            if (friend.ability_heat().unwrap() >=10 || (friend.attack_heat().unwrap() >= 10 && friend.unit_type() == Ranger)) && gc.can_overcharge(healer.id(),friend.id()) {
-                gc.overcharge(healer.id(),friend.id());
-                return Some(friend.id())
-            }
-        }
-    }
-    return None
-
-}
-
 fn try_javelin(gc: &mut GameController, nav: &mut Navigator, knight: &Unit) -> bool {
     let mut en_units = gc.sense_nearby_units_by_team(loc(knight),knight.ability_range().unwrap(),knight.team().other());
     en_units.sort_by_key(|en| nav.moves_between(&loc(knight),&loc(en)));
@@ -176,6 +176,29 @@ impl Navigator {
         self.a_star(unit, &start, end)
     }
 
+    /// Whether no planned route or standing unit claims `location` this turn or next.
+    pub fn is_open(&self, location: &MapLocation) -> bool {
+        let (x, y) = (location.x as Distance, location.y as Distance);
+        !self.reserved.contains(&(x, y, self.t))
+        && !self.reserved.contains(&(x, y, self.t + 1))
+        && !self.unmoved.contains(&(x, y))
+        && !self.enemies.contains(&(x, y))
+    }
+
+    /// Drops the route of a unit that jumped from `start` to `end` outside of navigation.
+    pub fn teleport(&mut self, id: u16, start: &MapLocation, end: &MapLocation) {
+        if self.targets.remove(&id).is_some() {
+            self.order.retain(|&other| other != id);
+            self.expiration.remove(&id).unwrap();
+            for (x, y, t, _) in self.routes.remove(&id).unwrap() {
+                self.reserved.remove(&(x, y, t));
+            }
+        }
+        self.moves.remove(&id);
+        self.unmoved.remove(&(start.x as Distance, start.y as Distance));
+        self.unmoved.insert((end.x as Distance, end.y as Distance));
+    }
+
     fn index(&self, x: Distance, y: Distance) -> usize { (y as usize * self.w as usize) + x as usize }
 
     fn to_direction(dx: Distance, dy: Distance) -> Option<Direction> {
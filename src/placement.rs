@@ -0,0 +1,250 @@
+use fnv::{FnvHashMap, FnvHashSet};
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use navigate::*;
+
+const SEARCH_RADIUS: i32 = 4;
+const MIN_OPEN: usize = 5;
+const FACTORY_SPACING: u32 = 8;
+
+const AROUND: [(i32, i32); 8] = [
+    (-1, 1), (0, 1), (1, 1),
+    (-1, 0), (1, 0),
+    (-1, -1), (0, -1), (1, -1),
+];
+
+type Karbonite = FnvHashMap<MapLocation, u32>;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// Chooses blueprint sites that don't wall off paths, keep factories apart,
+/// stay near karbonite, and stay away from the enemy.
+#[derive(Debug)]
+pub struct Planner {
+    w: i32,
+    h: i32,
+    planet: Planet,
+    terrain: Vec<bool>,
+    planned: FnvHashMap<UnitType, MapLocation>,
+}
+
+impl Planner {
+    pub fn new(gc: &GameController) -> Self {
+        let map = gc.starting_map(gc.planet());
+        let w = map.width as i32;
+        let h = map.height as i32;
+        let mut terrain = vec![false; (w * h) as usize];
+        for y in 0..h {
+            for x in 0..w {
+                terrain[(y * w + x) as usize] = map.is_passable_terrain[y as usize][x as usize];
+            }
+        }
+        Planner { w, h, planet: gc.planet(), terrain, planned: FnvHashMap::default() }
+    }
+
+    pub fn choose(&mut self, gc: &GameController, nav: &mut Navigator, building_type: UnitType,
+        workers: &Vec<Unit>, karbonite: &Karbonite, enemy: Option<MapLocation>) -> Option<MapLocation> {
+
+        // Mars has no factories and its rockets come from Earth
+        if gc.planet() != Planet::Earth || workers.len() == 0 { return None }
+
+        let structures = gc.units().into_iter()
+            .filter(|unit| unit.unit_type().is_structure())
+            .filter_map(|unit| unit.location().map_location().ok())
+            .collect::<Vec<_>>();
+        let open = self.open(&structures);
+        let cuts = articulation(self.w, self.h, &open);
+
+        // Keep the previous site while it's still usable, so workers don't wander
+        if let Some(&site) = self.planned.get(&building_type) {
+            if self.usable(gc, &open, &cuts, &site) && Self::ready(gc, workers, building_type, &site) {
+                return Some(site)
+            }
+        }
+
+        let factories = gc.my_units().into_iter()
+            .filter(|unit| unit.unit_type() == UnitType::Factory)
+            .filter_map(|unit| unit.location().map_location().ok())
+            .collect::<Vec<_>>();
+
+        let mut candidates = FnvHashSet::default();
+        for worker in workers {
+            let worker_loc = loc(worker);
+            for dy in -SEARCH_RADIUS..SEARCH_RADIUS + 1 {
+                for dx in -SEARCH_RADIUS..SEARCH_RADIUS + 1 {
+                    let (x, y) = (worker_loc.x + dx, worker_loc.y + dy);
+                    if x < 0 || x >= self.w || y < 0 || y >= self.h { continue }
+                    let site = MapLocation::new(self.planet, x, y);
+                    if self.usable(gc, &open, &cuts, &site) && !karbonite.contains_key(&site)
+                    && Self::ready(gc, workers, building_type, &site) {
+                        candidates.insert(site);
+                    }
+                }
+            }
+        }
+
+        let mut best = None;
+        for site in candidates {
+            let travel = workers.iter()
+                .map(|worker| Self::chebyshev(&loc(worker), &site))
+                .min()
+                .unwrap();
+            let harvest = karbonite.keys()
+                .map(|karb| nav.moves_between(&site, karb))
+                .min()
+                .unwrap_or(0);
+            let safety = enemy.map_or(0, |enemy| nav.moves_between(&site, &enemy));
+            let crowding = factories.iter()
+                .filter(|&&fact| building_type == UnitType::Factory && fact.distance_squared_to(site) < FACTORY_SPACING)
+                .count() as i32;
+
+            let score = safety - 3*travel - harvest - 10*crowding;
+            best = match best {
+                Some((_, top)) if top >= score => best,
+                _ => Some((site, score)),
+            };
+        }
+
+        let site = best.map(|(site, _)| site);
+        if let Some(site) = site {
+            self.planned.insert(building_type, site);
+        }
+        site
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize { (y * self.w + x) as usize }
+
+    /// Whether a worker next to `site` can lay the blueprint there right now.
+    fn ready(gc: &GameController, workers: &Vec<Unit>, building_type: UnitType, site: &MapLocation) -> bool {
+        workers.iter().any(|worker| {
+            let worker_loc = loc(worker);
+            worker_loc.is_adjacent_to(*site)
+            && gc.can_blueprint(worker.id(), building_type, worker_loc.direction_to(*site))
+        })
+    }
+
+    fn chebyshev(a: &MapLocation, b: &MapLocation) -> i32 {
+        i32::max((a.x - b.x).abs(), (a.y - b.y).abs())
+    }
+
+    fn open(&self, structures: &Vec<MapLocation>) -> Vec<bool> {
+        let mut open = self.terrain.clone();
+        for structure in structures {
+            if structure.planet == self.planet {
+                let index = self.index(structure.x, structure.y);
+                open[index] = false;
+            }
+        }
+        open
+    }
+
+    fn usable(&self, gc: &GameController, open: &Vec<bool>, cuts: &Vec<bool>, site: &MapLocation) -> bool {
+        let index = self.index(site.x, site.y);
+        if !open[index] || cuts[index] { return false }
+        if gc.can_sense_location(*site) && gc.has_unit_at_location(*site) { return false }
+        AROUND.iter()
+            .map(|&(dx, dy)| (site.x + dx, site.y + dy))
+            .filter(|&(x, y)| x >= 0 && x < self.w && y >= 0 && y < self.h)
+            .filter(|&(x, y)| open[self.index(x, y)])
+            .count() >= MIN_OPEN
+    }
+}
+
+/// Marks tiles of a `w` by `h` grid whose removal disconnects the open tiles.
+fn articulation(w: i32, h: i32, open: &Vec<bool>) -> Vec<bool> {
+    let size = (w * h) as usize;
+    let mut cuts = vec![false; size];
+    let mut depth = vec![usize::max_value(); size];
+    let mut low = vec![0; size];
+
+    for root in 0..size {
+        if !open[root] || depth[root] != usize::max_value() { continue }
+
+        // Iterative DFS holding (tile, parent, next neighbor to visit)
+        let mut stack = vec![(root, usize::max_value(), 0)];
+        let mut children = 0;
+        depth[root] = 0;
+        low[root] = 0;
+
+        loop {
+            let (node, parent, next) = match stack.last() { Some(&top) => top, None => break };
+            let (x, y) = ((node as i32) % w, (node as i32) / w);
+            if next < AROUND.len() {
+                let (dx, dy) = AROUND[next];
+                stack.last_mut().unwrap().2 += 1;
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || nx >= w || ny < 0 || ny >= h { continue }
+                let adj = (ny * w + nx) as usize;
+                if !open[adj] || adj == parent { continue }
+                if depth[adj] == usize::max_value() {
+                    depth[adj] = depth[node] + 1;
+                    low[adj] = depth[adj];
+                    if node == root { children += 1 }
+                    stack.push((adj, node, 0));
+                } else {
+                    low[node] = usize::min(low[node], depth[adj]);
+                }
+            } else {
+                stack.pop();
+                if parent != usize::max_value() {
+                    low[parent] = usize::min(low[parent], low[node]);
+                    if parent != root && low[node] >= depth[parent] {
+                        cuts[parent] = true;
+                    }
+                }
+            }
+        }
+        cuts[root] = children > 1;
+    }
+    cuts
+}
+
+#[cfg(test)]
+mod tests {
+    use placement::*;
+
+    fn grid(rows: &[&str]) -> (i32, i32, Vec<bool>) {
+        let open = rows.iter().rev()
+            .flat_map(|row| row.chars().map(|tile| tile == '.'))
+            .collect::<Vec<_>>();
+        (rows[0].len() as i32, rows.len() as i32, open)
+    }
+
+    fn marked(w: i32, cuts: &Vec<bool>) -> Vec<(i32, i32)> {
+        (0..cuts.len())
+            .filter(|&index| cuts[index])
+            .map(|index| (index as i32 % w, index as i32 / w))
+            .collect()
+    }
+
+    #[test]
+    fn test_corridor() {
+        let (w, h, open) = grid(&["....."]);
+        assert_eq!(marked(w, &articulation(w, h, &open)), vec![(1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn test_rooms() {
+        // Two rooms joined through a single tile
+        let (w, h, open) = grid(&[
+            "...#...",
+            ".......",
+            "...#...",
+        ]);
+        assert_eq!(marked(w, &articulation(w, h, &open)), vec![(3, 1)]);
+    }
+
+    #[test]
+    fn test_open() {
+        let (w, h, open) = grid(&[
+            "...",
+            "...",
+            "...",
+        ]);
+        assert!(marked(w, &articulation(w, h, &open)).is_empty());
+    }
+}
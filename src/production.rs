@@ -0,0 +1,152 @@
+use fnv::FnvHashMap;
+
+use engine::controller::*;
+use engine::unit::*;
+use intel::*;
+
+const COMBAT: [UnitType; 4] = [UnitType::Knight, UnitType::Ranger, UnitType::Mage, UnitType::Healer];
+const RUSH_DISTANCE: i32 = 30;
+const RESEARCH_BONUS: f64 = 0.25;
+const MAX_HEALER_SHARE: f64 = 0.3;
+
+/// Shared production targets for every factory. Targets are fractions of the
+/// army per unit type; factories each take the type furthest below its target.
+#[derive(Debug)]
+pub struct Production {
+    distance: i32,
+    targets: FnvHashMap<UnitType, f64>,
+    army: FnvHashMap<UnitType, usize>,
+    queued: FnvHashMap<UnitType, usize>,
+}
+
+impl Production {
+    /// `distance` is the walking distance between our start and the enemy's.
+    pub fn new(distance: i32) -> Self {
+        Production {
+            distance,
+            targets: FnvHashMap::default(),
+            army: FnvHashMap::default(),
+            queued: FnvHashMap::default(),
+        }
+    }
+
+    /// Recomputes targets for this turn; call once per turn before factories produce.
+    pub fn plan(&mut self, gc: &GameController, intel: &Intel, army: &FnvHashMap<UnitType, usize>) {
+        let research = gc.research_info().unwrap();
+        let levels = COMBAT.iter()
+            .map(|&unit_type| (unit_type, research.get_level(&unit_type)))
+            .collect::<FnvHashMap<_, _>>();
+        let enemy = intel.composition(gc.round());
+        self.update(&enemy, &levels, army);
+    }
+
+    pub fn targets(&self) -> &FnvHashMap<UnitType, f64> {
+        &self.targets
+    }
+
+    /// The unit type furthest below its target, counting units already queued this turn.
+    pub fn next(&self) -> UnitType {
+        let count = |unit_type: &UnitType| {
+            self.army.get(unit_type).cloned().unwrap_or(0) + self.queued.get(unit_type).cloned().unwrap_or(0)
+        };
+        let total = COMBAT.iter().map(|unit_type| count(unit_type)).sum::<usize>() + 1;
+
+        let mut best = (UnitType::Ranger, f64::NEG_INFINITY);
+        for unit_type in COMBAT.iter() {
+            let target = self.targets.get(unit_type).cloned().unwrap_or(0.0);
+            if target <= 0.0 { continue }
+            let deficit = target * total as f64 - count(unit_type) as f64;
+            if deficit > best.1 {
+                best = (*unit_type, deficit);
+            }
+        }
+        best.0
+    }
+
+    pub fn produced(&mut self, unit_type: UnitType) {
+        *self.queued.entry(unit_type).or_insert(0) += 1;
+    }
+
+    fn update(&mut self, enemy: &FnvHashMap<UnitType, usize>, levels: &FnvHashMap<UnitType, usize>,
+        army: &FnvHashMap<UnitType, usize>) {
+
+        let rush = self.distance <= RUSH_DISTANCE;
+        let mut weights = FnvHashMap::default();
+        weights.insert(UnitType::Knight, if rush { 3.0 } else { 1.0 });
+        weights.insert(UnitType::Ranger, if rush { 1.0 } else { 4.0 });
+        weights.insert(UnitType::Mage, 0.0);
+        weights.insert(UnitType::Healer, 1.0);
+
+        // Counter what the enemy fields, by share of their army
+        let seen = enemy.values().sum::<usize>();
+        if seen > 0 {
+            let share = |unit_type| enemy.get(&unit_type).cloned().unwrap_or(0) as f64 / seen as f64;
+            let (knights, rangers, mages, healers) =
+                (share(UnitType::Knight), share(UnitType::Ranger), share(UnitType::Mage), share(UnitType::Healer));
+            *weights.get_mut(&UnitType::Ranger).unwrap() += 3.0 * knights + 2.0 * mages;
+            *weights.get_mut(&UnitType::Mage).unwrap() += 1.0 * knights;
+            *weights.get_mut(&UnitType::Knight).unwrap() += 2.0 * rangers + 1.0 * healers;
+            *weights.get_mut(&UnitType::Healer).unwrap() += 1.0 * rangers;
+        }
+
+        // Favour the branches we've invested research in
+        for (unit_type, weight) in weights.iter_mut() {
+            let level = levels.get(unit_type).cloned().unwrap_or(0);
+            *weight *= 1.0 + RESEARCH_BONUS * level as f64;
+        }
+
+        let total = weights.values().sum::<f64>();
+        self.targets = weights.into_iter()
+            .map(|(unit_type, weight)| (unit_type, weight / total))
+            .collect();
+
+        // Healers only help when there's someone to heal
+        let healers = self.targets[&UnitType::Healer];
+        if healers > MAX_HEALER_SHARE {
+            let scale = (1.0 - MAX_HEALER_SHARE) / (1.0 - healers);
+            for (unit_type, target) in self.targets.iter_mut() {
+                *target = if *unit_type == UnitType::Healer { MAX_HEALER_SHARE } else { *target * scale };
+            }
+        }
+
+        self.army = army.clone();
+        self.queued.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use production::*;
+
+    #[test]
+    fn test_counter() {
+        let mut production = Production::new(100);
+        let mut enemy = FnvHashMap::default();
+        enemy.insert(UnitType::Ranger, 10);
+        let levels = FnvHashMap::default();
+        let army = FnvHashMap::default();
+
+        production.update(&FnvHashMap::default(), &levels, &army);
+        let before = production.targets()[&UnitType::Knight];
+        production.update(&enemy, &levels, &army);
+        assert!(production.targets()[&UnitType::Knight] > before);
+
+        let sum = production.targets().values().sum::<f64>();
+        assert!((sum - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_concert() {
+        let mut production = Production::new(100);
+        let mut army = FnvHashMap::default();
+        army.insert(UnitType::Ranger, 4);
+        army.insert(UnitType::Knight, 1);
+        production.update(&FnvHashMap::default(), &FnvHashMap::default(), &army);
+
+        // Factories see each other's picks, so they don't all produce the same type
+        let first = production.next();
+        assert_eq!(first, UnitType::Healer);
+        production.produced(first);
+        assert_eq!(production.next(), UnitType::Ranger);
+    }
+}
@@ -0,0 +1,165 @@
+use fnv::FnvHashMap;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::map::*;
+use engine::world::*;
+use intel::*;
+
+const ARMY_FRESH: Rounds = 20;
+const CLUSTER: u32 = 25;
+const DEFEND_RADIUS: u32 = 100;
+const CLEARED: u32 = 16;
+
+// Target scores; higher wins
+const ARMY: i32 = 10;
+const STRUCTURE: i32 = 8;
+const GUESS: i32 = 5;
+const LANDING: i32 = 2;
+
+/// A way the map may mirror onto itself.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Symmetry {
+    Horizontal,
+    Vertical,
+    Rotational,
+}
+
+impl Symmetry {
+    fn apply(&self, w: i32, h: i32, x: i32, y: i32) -> (i32, i32) {
+        match *self {
+            Symmetry::Horizontal => (w - 1 - x, y),
+            Symmetry::Vertical => (x, h - 1 - y),
+            Symmetry::Rotational => (w - 1 - x, h - 1 - y),
+        }
+    }
+}
+
+/// Symmetries under which terrain and karbonite look the same.
+fn symmetries(map: &PlanetMap) -> Vec<Symmetry> {
+    let (w, h) = (map.width as i32, map.height as i32);
+    [Symmetry::Horizontal, Symmetry::Vertical, Symmetry::Rotational].iter()
+        .cloned()
+        .filter(|symmetry| (0..h).all(|y| (0..w).all(|x| {
+            let (mx, my) = symmetry.apply(w, h, x, y);
+            let (x, y, mx, my) = (x as usize, y as usize, mx as usize, my as usize);
+            map.is_passable_terrain[y][x] == map.is_passable_terrain[my][mx]
+            && map.initial_karbonite[y][x] == map.initial_karbonite[my][mx]
+        })))
+        .collect()
+}
+
+/// Chooses where each squad should go from enemy memory, map symmetry and
+/// how long ago we last saw each part of the map.
+#[derive(Debug)]
+pub struct Rally {
+    planet: Planet,
+    passable: Vec<MapLocation>,
+    guesses: Vec<MapLocation>,
+    attack: Option<MapLocation>,
+    defend: Option<MapLocation>,
+    scout: Option<MapLocation>,
+}
+
+impl Rally {
+    pub fn new(gc: &GameController) -> Self {
+        let planet = gc.planet();
+        let map = gc.starting_map(planet);
+        let (w, h) = (map.width as i32, map.height as i32);
+
+        let mut passable = Vec::new();
+        for y in 0..h {
+            for x in 0..w {
+                if map.is_passable_terrain[y as usize][x as usize] {
+                    passable.push(MapLocation::new(planet, x, y));
+                }
+            }
+        }
+
+        // Enemy starts we know of, and mirror images of our own
+        let mut guesses = Vec::new();
+        let symmetries = symmetries(map);
+        for unit in &map.initial_units {
+            let unit_loc = unit.location().map_location().unwrap();
+            if unit.team() != gc.team() {
+                guesses.push(unit_loc);
+                continue
+            }
+            for symmetry in &symmetries {
+                let (x, y) = symmetry.apply(w, h, unit_loc.x, unit_loc.y);
+                guesses.push(MapLocation::new(planet, x, y));
+            }
+        }
+        guesses.sort_by_key(|guess| (guess.x, guess.y));
+        guesses.dedup();
+
+        Rally { planet, passable, guesses, attack: None, defend: None, scout: None }
+    }
+
+    pub fn attack(&self) -> Option<MapLocation> { self.attack }
+    pub fn defend(&self) -> Option<MapLocation> { self.defend }
+    pub fn scout(&self) -> Option<MapLocation> { self.scout }
+
+    /// Replans every squad's rally. `seen` holds turns since each tile was last
+    /// in sight, and `landings` are where our rockets are coming down.
+    pub fn plan(&mut self, gc: &GameController, intel: &Intel, seen: &FnvHashMap<MapLocation, i32>,
+        home: Option<MapLocation>, landings: &Vec<MapLocation>) {
+
+        let round = gc.round();
+        let planet = self.planet;
+        let sightings = intel.sightings().into_iter()
+            .filter(|sighting| sighting.location.planet == planet)
+            .collect::<Vec<_>>();
+        let armies = sightings.iter()
+            .filter(|sighting| sighting.unit_type.is_robot() && sighting.round + ARMY_FRESH >= round)
+            .collect::<Vec<_>>();
+
+        // Drop guesses we can see are empty
+        self.guesses.retain(|&guess| !gc.can_sense_location(guess)
+            || sightings.iter().any(|sighting| sighting.location.distance_squared_to(guess) <= CLEARED));
+
+        // Score each candidate, the biggest army clusters above everything else
+        let mut candidates = Vec::new();
+        for army in &armies {
+            let cluster = armies.iter()
+                .filter(|other| other.location.distance_squared_to(army.location) <= CLUSTER)
+                .count() as i32;
+            candidates.push((army.location, ARMY + cluster));
+        }
+        for sighting in sightings.iter().filter(|sighting| sighting.unit_type.is_structure()) {
+            candidates.push((sighting.location, STRUCTURE));
+        }
+        for &guess in &self.guesses {
+            candidates.push((guess, GUESS));
+        }
+        for &landing in landings {
+            candidates.push((landing, LANDING));
+        }
+
+        // Closer to home breaks ties
+        let distance = |location: &MapLocation| home.map_or(0, |home| home.distance_squared_to(*location) as i32);
+        self.attack = candidates.iter()
+            .max_by_key(|&&(location, score)| (score, -distance(&location)))
+            .map(|&(location, _)| location);
+
+        self.defend = armies.iter()
+            .filter(|army| home.map_or(false, |home| home.distance_squared_to(army.location) <= DEFEND_RADIUS))
+            .min_by_key(|army| distance(&army.location))
+            .map(|army| army.location)
+            .or(home);
+
+        // Keep scouting the same tile until it comes into view
+        let attack = self.attack;
+        let keep = self.scout.map_or(false, |scout| !gc.can_sense_location(scout) && Some(scout) != attack);
+        if !keep {
+            let staleness = |location: &MapLocation| seen.get(location).cloned().unwrap_or(i32::max_value());
+            self.scout = self.passable.iter()
+                .filter(|&&location| Some(location) != attack)
+                .max_by_key(|&location| (staleness(location), -distance(location)))
+                .cloned();
+        }
+        if self.attack.is_none() {
+            self.attack = self.scout;
+        }
+    }
+}
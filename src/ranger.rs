@@ -0,0 +1,132 @@
+use fnv::FnvHashMap;
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+use combat::*;
+use intel::*;
+
+const SNIPE_LEVEL: usize = 3;
+const FRESH: Rounds = 5;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// Picks this turn's tile for a ranger. With its gun ready it steps to where an
+/// enemy is in range but outside its blind spot; after firing it steps back out
+/// of enemy reach. `None` when it can't move or sees no enemy.
+pub fn kite(gc: &GameController, ranger: &Unit) -> Option<MapLocation> {
+    if !gc.is_move_ready(ranger.id()) { return None }
+
+    let ranger_loc = loc(ranger);
+    let enemies = gc.sense_nearby_units_by_team(ranger_loc, ranger.vision_range(), ranger.team().other());
+    if enemies.len() == 0 { return None }
+
+    let range = ranger.attack_range().unwrap();
+    let blind = ranger.ranger_cannot_attack_range().unwrap();
+    let ready = gc.is_attack_ready(ranger.id());
+
+    let mut best = None;
+    // Staying put comes first so it wins ties
+    let tiles = Some(ranger_loc).into_iter()
+        .chain(Direction::all().into_iter().map(|dir| ranger_loc.add(dir)));
+    for tile in tiles {
+        if tile != ranger_loc && !gc.can_move(ranger.id(), ranger_loc.direction_to(tile)) { continue }
+
+        let targets = enemies.iter()
+            .map(|enemy| loc(enemy).distance_squared_to(tile))
+            .any(|dist| dist > blind && dist <= range);
+        let danger = enemies.iter()
+            .filter(|enemy| loc(enemy).distance_squared_to(tile) <= reach(enemy))
+            .count() as i32;
+
+        let score = if ready { (targets as i32, -danger) } else { (-danger, targets as i32) };
+        best = match best {
+            Some((_, top)) if top >= score => best,
+            _ => Some((tile, score)),
+        };
+    }
+    best.map(|(tile, _)| tile)
+}
+
+/// How much a remembered enemy is worth a snipe. Structures stay put; units
+/// only while the sighting is fresh enough that they're likely still there.
+fn snipe_value(sighting: &Sighting, round: Rounds) -> Option<i32> {
+    match sighting.unit_type {
+        UnitType::Rocket => Some(6),
+        UnitType::Factory => Some(5),
+        UnitType::Mage | UnitType::Healer if sighting.round + FRESH >= round => Some(4),
+        _ => None,
+    }
+}
+
+/// Sends idle rangers' snipes at remembered high-value targets, tracking damage
+/// already on its way so targets aren't sniped past dead.
+#[derive(Debug)]
+pub struct Snipes {
+    /// Snipes in flight by target, as the round each lands and its damage.
+    pending: FnvHashMap<UnitID, Vec<(Rounds, u32)>>,
+}
+
+impl Snipes {
+    pub fn new() -> Self {
+        Snipes { pending: FnvHashMap::default() }
+    }
+
+    fn pending(&self, id: UnitID) -> u32 {
+        self.pending.get(&id).map_or(0, |shots| shots.iter().map(|&(_, damage)| damage).sum())
+    }
+
+    /// Begins snipes from rangers that aren't needed in a fight and returns them.
+    /// Healers overcharge rangers with hot abilities, so an overcharged ranger
+    /// passed back in here snipes again the same turn.
+    pub fn plan(&mut self, gc: &mut GameController, intel: &Intel, rangers: &Vec<Unit>) -> Vec<UnitID> {
+        if gc.research_info().unwrap().get_level(&UnitType::Ranger) < SNIPE_LEVEL { return Vec::new() }
+
+        let round = gc.round();
+        let planet = gc.planet();
+
+        // Landed snipes show up in the next sighting, and lost targets can't be hit
+        self.pending.retain(|id, shots| {
+            shots.retain(|&(lands, _)| lands > round);
+            shots.len() > 0 && intel.get(*id).is_some()
+        });
+
+        let mut targets = intel.sightings().into_iter()
+            .filter(|sighting| sighting.location.planet == planet)
+            .filter(|sighting| self.pending(sighting.id) < sighting.health)
+            .filter_map(|sighting| snipe_value(sighting, round).map(|value| (value, sighting)))
+            .collect::<Vec<_>>();
+        targets.sort_by_key(|&(value, sighting)| (-value, sighting.health));
+
+        let mut snipers = rangers.iter()
+            .filter(|ranger| ranger.location().is_on_map() && gc.is_begin_snipe_ready(ranger.id()))
+            .filter(|ranger| gc.sense_nearby_units_by_team(loc(ranger), ranger.vision_range(), ranger.team().other()).len() == 0)
+            .collect::<Vec<_>>();
+
+        let mut fired = Vec::new();
+        for (_, sighting) in targets {
+            let mut dealt = self.pending(sighting.id);
+            let mut rejected = Vec::new();
+            while dealt < sighting.health {
+                let sniper = match snipers.pop() { Some(sniper) => sniper, None => break };
+                if gc.can_begin_snipe(sniper.id(), sighting.location) {
+                    gc.begin_snipe(sniper.id(), sighting.location);
+                    let lands = round + sniper.ranger_max_countdown().unwrap();
+                    let damage = sniper.damage().unwrap() as u32;
+                    self.pending.entry(sighting.id).or_insert_with(Vec::new).push((lands, damage));
+                    dealt += damage;
+                    fired.push(sniper.id());
+                } else {
+                    rejected.push(sniper);
+                }
+            }
+            // Rangers that couldn't reach this target may still reach the next
+            snipers.extend(rejected);
+            if snipers.len() == 0 { break }
+        }
+        fired
+    }
+}
@@ -0,0 +1,140 @@
+use fnv::FnvHashMap;
+
+use engine::controller::*;
+use engine::unit::*;
+use engine::world::*;
+use intel::*;
+use production::*;
+
+const BRANCHES: [UnitType; 6] = [
+    UnitType::Worker, UnitType::Knight, UnitType::Ranger,
+    UnitType::Mage, UnitType::Healer, UnitType::Rocket,
+];
+const PLANNED: usize = 6;
+const ROCKET_DEADLINE: Rounds = 300;
+
+fn max_level(branch: UnitType) -> usize {
+    match branch {
+        UnitType::Worker | UnitType::Mage => 4,
+        UnitType::Knight | UnitType::Ranger | UnitType::Healer | UnitType::Rocket => 3,
+        _ => 0,
+    }
+}
+
+/// Keeps the research queue in line with the game phase, the enemy's army
+/// and what our factories are producing.
+#[derive(Debug)]
+pub struct Research {
+    /// Levels researched across all branches when the queue was last written.
+    done: usize,
+}
+
+impl Research {
+    pub fn new() -> Self {
+        Research { done: 0 }
+    }
+
+    /// Rewrites the queue when it runs dry or a level has just finished. Resetting
+    /// throws away the progress of the branch in progress, so the queue is left
+    /// alone while that branch is partway done.
+    pub fn update(&mut self, gc: &mut GameController, production: &Production, intel: &Intel) {
+        let info = gc.research_info().unwrap();
+        let levels = BRANCHES.iter()
+            .map(|&branch| (branch, info.get_level(&branch)))
+            .collect::<FnvHashMap<_, _>>();
+        let done = levels.values().sum::<usize>();
+        if info.queue().len() > 0 && done == self.done { return }
+
+        let planned = Self::order(gc.round(), &levels, production.targets(), &intel.composition(gc.round()));
+        gc.reset_research();
+        for &branch in &planned {
+            gc.queue_research(branch);
+        }
+        self.done = done;
+    }
+
+    /// Greedily picks the next upgrades by value, given the levels already reached.
+    fn order(round: Rounds, levels: &FnvHashMap<UnitType, usize>, targets: &FnvHashMap<UnitType, f64>,
+        enemy: &FnvHashMap<UnitType, usize>) -> Vec<UnitType> {
+
+        let mut levels = levels.clone();
+        let mut order = Vec::new();
+        while order.len() < PLANNED {
+            let mut best: Option<(UnitType, f64)> = None;
+            for &branch in BRANCHES.iter() {
+                let level = levels.get(&branch).cloned().unwrap_or(0) + 1;
+                if level > max_level(branch) { continue }
+                let value = Self::value(branch, level, round, targets, enemy);
+                best = match best {
+                    Some((_, top)) if top >= value => best,
+                    _ => Some((branch, value)),
+                };
+            }
+            match best {
+                Some((branch, _)) => {
+                    order.push(branch);
+                    *levels.entry(branch).or_insert(0) += 1;
+                }
+                None => break,
+            }
+        }
+        order
+    }
+
+    fn value(branch: UnitType, level: usize, round: Rounds, targets: &FnvHashMap<UnitType, f64>,
+        enemy: &FnvHashMap<UnitType, usize>) -> f64 {
+
+        let seen = enemy.values().sum::<usize>();
+        let share = |unit_type| {
+            if seen == 0 { 0.0 } else { enemy.get(&unit_type).cloned().unwrap_or(0) as f64 / seen as f64 }
+        };
+
+        match branch {
+            // Faster harvesting only pays off early
+            UnitType::Worker => if level == 1 && round < 100 { 2.0 } else { 0.1 },
+            // Rockets become urgent as the flood approaches; later levels are nice to have
+            UnitType::Rocket => if level == 1 {
+                0.5 + 3.0 * f64::min(1.0, round as f64 / ROCKET_DEADLINE as f64)
+            } else {
+                0.3
+            },
+            _ => {
+                let target = targets.get(&branch).cloned().unwrap_or(0.0);
+                let counter = match branch {
+                    UnitType::Knight => share(UnitType::Ranger),
+                    UnitType::Ranger | UnitType::Mage => share(UnitType::Knight),
+                    UnitType::Healer => share(UnitType::Ranger) + share(UnitType::Mage),
+                    _ => 0.0,
+                };
+                (4.0 * target + counter) / level as f64
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use research::*;
+
+    #[test]
+    fn test_order() {
+        let mut levels = FnvHashMap::default();
+        let mut targets = FnvHashMap::default();
+        targets.insert(UnitType::Ranger, 0.7);
+        targets.insert(UnitType::Healer, 0.3);
+        let enemy = FnvHashMap::default();
+
+        let early = Research::order(1, &levels, &targets, &enemy);
+        assert_eq!(early[0], UnitType::Ranger);
+        assert_eq!(early.iter().filter(|&&branch| branch == UnitType::Knight).count(), 0);
+
+        // Late in the game rockets come first
+        let late = Research::order(400, &levels, &targets, &enemy);
+        assert_eq!(late[0], UnitType::Rocket);
+
+        // Maxed branches aren't queued again
+        levels.insert(UnitType::Ranger, 3);
+        let maxed = Research::order(1, &levels, &targets, &enemy);
+        assert!(!maxed.contains(&UnitType::Ranger));
+    }
+}
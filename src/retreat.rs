@@ -0,0 +1,95 @@
+use fnv::FnvHashSet;
+
+use engine::location::*;
+use engine::unit::*;
+use navigate::*;
+
+const RETREAT_BELOW: f64 = 0.35;
+const RETURN_ABOVE: f64 = 0.8;
+const CLUSTER: u32 = 25;
+const CLUSTER_PULL: i32 = 2;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// Whether a unit at `ratio` of its max health should be falling back. Units
+/// leave below one threshold and return above a higher one, so they don't
+/// turn back and forth at the edge.
+pub fn falls_back(retreating: bool, ratio: f64) -> bool {
+    if retreating { ratio < RETURN_ABOVE } else { ratio < RETREAT_BELOW }
+}
+
+/// Keeps track of wounded units sent back to heal.
+#[derive(Debug)]
+pub struct Wounded {
+    retreating: FnvHashSet<UnitID>,
+}
+
+impl Wounded {
+    pub fn new() -> Self {
+        Wounded { retreating: FnvHashSet::default() }
+    }
+
+    /// Re-evaluates every unit; units not passed in are forgotten. Nobody falls
+    /// back without a healer on the map, since nothing else restores health.
+    pub fn update(&mut self, units: &Vec<Unit>, healers: &Vec<Unit>) {
+        if !healers.iter().any(|healer| healer.location().is_on_map()) {
+            self.retreating.clear();
+            return
+        }
+        let retreating = units.iter()
+            .filter(|unit| {
+                let ratio = unit.health() as f64 / unit.max_health() as f64;
+                falls_back(self.retreating.contains(&unit.id()), ratio)
+            })
+            .map(|unit| unit.id())
+            .collect();
+        self.retreating = retreating;
+    }
+
+    pub fn is_retreating(&self, id: UnitID) -> bool {
+        self.retreating.contains(&id)
+    }
+
+    /// The nearest healer cluster or factory, with bigger clusters drawing from further away.
+    /// Factories only gather units for the healers, so they're skipped when there are none.
+    pub fn refuge(&self, nav: &mut Navigator, unit: &Unit, healers: &Vec<Unit>, factories: &Vec<Unit>) -> Option<MapLocation> {
+        let unit_loc = loc(unit);
+        let mut best = None;
+        for healer in healers.iter().filter(|healer| healer.location().is_on_map()) {
+            let healer_loc = loc(healer);
+            let cluster = healers.iter()
+                .filter(|other| other.location().is_on_map() && loc(other).distance_squared_to(healer_loc) <= CLUSTER)
+                .count() as i32;
+            let score = nav.moves_between(&unit_loc, &healer_loc) - CLUSTER_PULL * cluster;
+            best = match best {
+                Some((_, top)) if top <= score => best,
+                _ => Some((healer_loc, score)),
+            };
+        }
+        if best.is_none() { return None }
+        for factory in factories {
+            let factory_loc = loc(factory);
+            let score = nav.moves_between(&unit_loc, &factory_loc);
+            best = match best {
+                Some((_, top)) if top <= score => best,
+                _ => Some((factory_loc, score)),
+            };
+        }
+        best.map(|(location, _)| location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use retreat::*;
+
+    #[test]
+    fn test_hysteresis() {
+        assert!(!falls_back(false, 0.5));
+        assert!(falls_back(false, 0.3));
+        assert!(falls_back(true, 0.5));
+        assert!(!falls_back(true, 0.9));
+    }
+}
@@ -0,0 +1,259 @@
+use fnv::{FnvHashMap, FnvHashSet};
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+
+const FLOOD_ROUND: Rounds = 750;
+const FLOOD_MARGIN: Rounds = 5;
+const THREAT_RADIUS: u32 = 50;
+const SYNC_WAIT: Rounds = 30;
+
+/// Desired rocket cargo, in boarding priority order.
+/// Seats beyond the listed counts go to any combat robot when `flexible` is set.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RocketManifest {
+    cargo: Vec<(UnitType, usize)>,
+    flexible: bool,
+}
+
+impl RocketManifest {
+    pub fn new(cargo: Vec<(UnitType, usize)>, flexible: bool) -> Self {
+        RocketManifest { cargo, flexible }
+    }
+
+    /// Early rockets seed Mars with workers; late ones carry whatever's left before the flood.
+    pub fn for_round(round: Rounds) -> Self {
+        use engine::unit::UnitType::*;
+        if round < 400 {
+            RocketManifest::new(vec![(Worker, 2), (Ranger, 3), (Healer, 2), (Knight, 1)], false)
+        } else if round < 650 {
+            RocketManifest::new(vec![(Worker, 1), (Ranger, 4), (Healer, 2), (Knight, 1)], false)
+        } else {
+            RocketManifest::new(vec![(Ranger, 3), (Healer, 2), (Worker, 1)], true)
+        }
+    }
+
+    /// Caps each listed seat at how many of that type we have, opening the rest to
+    /// any combat robot so rockets don't wait on units that don't exist.
+    pub fn relaxed(mut self, army: &FnvHashMap<UnitType, usize>) -> Self {
+        for seat in self.cargo.iter_mut() {
+            let have = army.get(&seat.0).cloned().unwrap_or(0);
+            if have < seat.1 {
+                seat.1 = have;
+                self.flexible = true;
+            }
+        }
+        self
+    }
+
+    /// Remaining seats as (priority, type), where `None` accepts any combat robot.
+    pub fn open(&self, garrison: &Vec<UnitType>, capacity: usize) -> Vec<(usize, Option<UnitType>)> {
+        let mut seats = Vec::new();
+        for (priority, &(unit_type, count)) in self.cargo.iter().enumerate() {
+            let have = garrison.iter().filter(|&&other| other == unit_type).count();
+            for _ in usize::min(have, count)..count { seats.push((priority, Some(unit_type))) }
+        }
+
+        let free = capacity.saturating_sub(garrison.len());
+        seats.truncate(free);
+        if self.flexible {
+            for _ in seats.len()..free { seats.push((self.cargo.len(), None)) }
+        }
+        seats
+    }
+
+    pub fn accepts(&self, garrison: &Vec<UnitType>, capacity: usize, unit_type: UnitType) -> bool {
+        self.open(garrison, capacity).iter().any(|&(_, seat)| match seat {
+            Some(seat) => seat == unit_type,
+            None => unit_type.is_robot() && unit_type != UnitType::Worker,
+        })
+    }
+
+    pub fn satisfied(&self, garrison: &Vec<UnitType>, capacity: usize) -> bool {
+        garrison.len() >= capacity || self.open(garrison, capacity).iter().all(|&(_, seat)| seat.is_none())
+    }
+}
+
+pub fn garrison_types(gc: &GameController, rocket: &Unit) -> Vec<UnitType> {
+    rocket.structure_garrison().unwrap().iter()
+        .filter_map(|&id| gc.unit(id).ok())
+        .map(|unit| unit.unit_type())
+        .collect::<Vec<_>>()
+}
+
+/// How launches of separate rockets relate to each other in time.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Launch {
+    /// At most one rocket per `n` rounds, so arrivals trickle onto Mars.
+    Staggered(Rounds),
+    /// Every ready rocket waits for the others still loading, so arrivals land
+    /// together; after a while the ready ones go without them.
+    Synchronized,
+}
+
+#[derive(Debug)]
+pub struct Scheduler {
+    mode: Launch,
+    manifest: RocketManifest,
+    last: Option<Rounds>,
+    /// Round ready rockets started waiting on the rest.
+    held: Option<Rounds>,
+    passable: Vec<MapLocation>,
+    sites: FnvHashMap<UnitID, MapLocation>,
+    claimed: Vec<MapLocation>,
+}
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+impl Scheduler {
+    pub fn new(gc: &GameController, mode: Launch, manifest: RocketManifest) -> Self {
+        let map = gc.starting_map(Planet::Mars);
+        let mut passable = Vec::new();
+        for y in 0..map.height {
+            for x in 0..map.width {
+                if map.is_passable_terrain[y][x] {
+                    passable.push(MapLocation::new(Planet::Mars, x as i32, y as i32));
+                }
+            }
+        }
+        Scheduler { mode, manifest, last: None, held: None, passable,
+            sites: FnvHashMap::default(),
+            claimed: Vec::new(),
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: Launch) { self.mode = mode }
+
+    pub fn set_manifest(&mut self, manifest: RocketManifest) { self.manifest = manifest }
+
+    pub fn manifest(&self) -> &RocketManifest { &self.manifest }
+
+    /// Picks a distinct landing site for each rocket without one, preferring `preferred`.
+    /// Returns the newly chosen sites so they can be announced.
+    pub fn plan(&mut self, rockets: &Vec<Unit>, preferred: &Vec<MapLocation>) -> Vec<MapLocation> {
+        let live = rockets.iter().map(|rocket| rocket.id()).collect::<FnvHashSet<_>>();
+        self.sites.retain(|id, _| live.contains(id));
+
+        let mut planned = Vec::new();
+        for rocket in rockets {
+            if self.sites.contains_key(&rocket.id()) { continue }
+            let taken = self.claimed.iter()
+                .chain(self.sites.values())
+                .cloned()
+                .collect::<Vec<_>>();
+
+            // Fall back to any passable tile once preferred sites are crowded
+            let best = {
+                let candidates = if preferred.iter().any(|site| Self::spread(site, &taken) > 2) {
+                    preferred
+                } else {
+                    &self.passable
+                };
+                candidates.iter()
+                    .max_by_key(|site| Self::spread(site, &taken))
+                    .cloned()
+            };
+
+            if let Some(site) = best {
+                self.sites.insert(rocket.id(), site);
+                planned.push(site);
+            }
+        }
+        planned
+    }
+
+    /// Decides which rockets launch this round. `boarding` maps units to the rocket
+    /// they're headed for; empty rockets nobody is boarding don't hold others back.
+    pub fn launches(&mut self, gc: &GameController, rockets: &Vec<Unit>,
+        boarding: &FnvHashMap<UnitID, MapLocation>) -> Vec<(UnitID, MapLocation)> {
+
+        let round = gc.round();
+        let orbit = gc.orbit_pattern();
+        let window = orbit.duration(round) <= orbit.duration(round + 1) + 1;
+        let flood = round + FLOOD_MARGIN >= FLOOD_ROUND;
+
+        let mut forced = Vec::new();
+        let mut ready = Vec::new();
+        let mut waiting = 0;
+        for rocket in rockets {
+            if rocket.rocket_is_used().unwrap() { continue }
+            let site = match self.sites.get(&rocket.id()) {
+                Some(&site) => site,
+                None => continue,
+            };
+            let garrison = garrison_types(gc, rocket);
+            let capacity = rocket.structure_max_capacity().unwrap();
+            let attacked = rocket.health() < rocket.max_health()
+                || gc.sense_nearby_units_by_team(loc(rocket), THREAT_RADIUS, gc.team().other())
+                    .iter()
+                    .any(|en| en.unit_type().is_robot() && en.unit_type() != UnitType::Worker);
+
+            if garrison.len() > 0 && (attacked || flood) {
+                forced.push((rocket.id(), site));
+            } else if self.manifest.satisfied(&garrison, capacity) {
+                ready.push((rocket.id(), site));
+            } else if garrison.len() > 0 || boarding.values().any(|&target| target == loc(rocket)) {
+                waiting += 1;
+            }
+        }
+
+        if !window {
+            return forced
+        }
+
+        match self.mode {
+            Launch::Synchronized => {
+                if waiting > 0 && ready.len() > 0 {
+                    let held = *self.held.get_or_insert(round);
+                    if round < held + SYNC_WAIT { ready.clear() }
+                }
+            }
+            Launch::Staggered(gap) => {
+                let spaced = self.last.map_or(true, |last| round >= last + gap);
+                ready.truncate(if spaced { 1 } else { 0 });
+            }
+        }
+        forced.into_iter().chain(ready.into_iter()).collect()
+    }
+
+    pub fn launched(&mut self, round: Rounds, rocket: UnitID) {
+        if let Some(site) = self.sites.remove(&rocket) {
+            self.claimed.push(site);
+        }
+        self.last = Some(round);
+        self.held = None;
+    }
+
+    fn spread(site: &MapLocation, taken: &Vec<MapLocation>) -> u32 {
+        taken.iter()
+            .map(|other| other.distance_squared_to(*site))
+            .min()
+            .unwrap_or(u32::max_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::*;
+
+    #[test]
+    fn test_relaxed() {
+        let manifest = RocketManifest::new(vec![(UnitType::Ranger, 2), (UnitType::Healer, 1)], false);
+        let mut army = FnvHashMap::default();
+        army.insert(UnitType::Ranger, 2);
+        army.insert(UnitType::Healer, 1);
+        assert_eq!(manifest.clone().relaxed(&army), manifest);
+
+        // Without healers the healer seat goes to whoever is around
+        army.remove(&UnitType::Healer);
+        let garrison = vec![UnitType::Ranger, UnitType::Ranger];
+        assert!(!manifest.satisfied(&garrison, 3));
+        let relaxed = manifest.relaxed(&army);
+        assert!(relaxed.satisfied(&garrison, 3));
+        assert!(relaxed.accepts(&garrison, 3, UnitType::Mage));
+    }
+}
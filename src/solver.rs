@@ -0,0 +1,539 @@
+use fnv::FnvHashMap;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::hash::Hash;
+
+/// Any integer cost that widens losslessly into `i64`.
+pub trait Cost: Copy + Into<i64> {}
+impl<T: Copy + Into<i64>> Cost for T {}
+
+#[derive(Debug)]
+struct Edge {
+    to: usize,
+    cap: i64,
+    cost: i64,
+}
+
+/// Minimum cost assignment of rows to columns, using row and column potentials.
+/// Each row gets a distinct column when there are at least as many columns as rows;
+/// otherwise only the cheapest `cols` rows are assigned.
+pub fn hungarian<C: Cost>(matrix: &Vec<Vec<C>>) -> Vec<Option<usize>> {
+    let rows = matrix.len();
+    if rows == 0 { return Vec::new() }
+    let cols = matrix[0].len();
+    if cols == 0 { return vec![None; rows] }
+
+    // Solve the transpose and invert when there are more rows than columns
+    if rows > cols {
+        let transposed = (0..cols)
+            .map(|col| matrix.iter().map(|row| row[col]).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut assignment = vec![None; rows];
+        for (col, row) in hungarian(&transposed).into_iter().enumerate() {
+            if let Some(row) = row { assignment[row] = Some(col) }
+        }
+        return assignment
+    }
+
+    // Potentials and matching are 1-indexed, with column 0 as a sentinel
+    let inf = i64::max_value();
+    let mut u = vec![0i64; rows + 1];
+    let mut v = vec![0i64; cols + 1];
+    let mut matched = vec![0usize; cols + 1];
+    let mut way = vec![0usize; cols + 1];
+
+    for row in 1..rows + 1 {
+        matched[0] = row;
+        let mut col0 = 0;
+        let mut minv = vec![inf; cols + 1];
+        let mut used = vec![false; cols + 1];
+
+        // Grow alternating tree until a free column is reached
+        loop {
+            used[col0] = true;
+            let row0 = matched[col0];
+            let mut delta = inf;
+            let mut col1 = 0;
+            for col in 1..cols + 1 {
+                if used[col] { continue }
+                let reduced = matrix[row0 - 1][col - 1].into() - u[row0] - v[col];
+                if reduced < minv[col] {
+                    minv[col] = reduced;
+                    way[col] = col0;
+                }
+                if minv[col] < delta {
+                    delta = minv[col];
+                    col1 = col;
+                }
+            }
+            for col in 0..cols + 1 {
+                if used[col] {
+                    u[matched[col]] += delta;
+                    v[col] -= delta;
+                } else {
+                    minv[col] -= delta;
+                }
+            }
+            col0 = col1;
+            if matched[col0] == 0 { break }
+        }
+
+        // Augment along the path back to the sentinel
+        loop {
+            let col1 = way[col0];
+            matched[col0] = matched[col1];
+            col0 = col1;
+            if col0 == 0 { break }
+        }
+    }
+
+    let mut assignment = vec![None; rows];
+    for col in 1..cols + 1 {
+        if matched[col] != 0 { assignment[matched[col] - 1] = Some(col - 1) }
+    }
+    assignment
+}
+
+
+/// Minimum cost assignment of agents (rows) to tasks (columns), where task `j` accepts
+/// up to `capacities[j]` agents. Assigns as many agents as capacity allows.
+pub fn assign<C: Cost>(costs: &Vec<Vec<C>>, capacities: &Vec<usize>) -> Vec<Option<usize>> {
+    let agents = costs.len();
+    let tasks = capacities.len();
+    if agents == 0 || tasks == 0 { return vec![None; agents] }
+
+    // Source, agents, tasks, sink
+    let source = 0;
+    let sink = agents + tasks + 1;
+    let mut edges = Vec::new();
+    let mut graph = vec![Vec::new(); sink + 1];
+    {
+        let mut add = |from: usize, to: usize, cap: i64, cost: i64| {
+            graph[from].push(edges.len());
+            edges.push(Edge { to, cap, cost });
+            graph[to].push(edges.len());
+            edges.push(Edge { to: from, cap: 0, cost: -cost });
+        };
+        for agent in 0..agents {
+            add(source, agent + 1, 1, 0);
+            for task in 0..tasks {
+                add(agent + 1, agents + task + 1, 1, costs[agent][task].into());
+            }
+        }
+        for task in 0..tasks {
+            add(agents + task + 1, sink, capacities[task] as i64, 0);
+        }
+    }
+
+    // Initial potentials keep every reduced cost non-negative
+    let inf = i64::max_value();
+    let mut potential = vec![0i64; sink + 1];
+    for task in 0..tasks {
+        potential[agents + task + 1] = (0..agents).map(|agent| costs[agent][task].into()).min().unwrap();
+    }
+    potential[sink] = (0..tasks).map(|task| potential[agents + task + 1]).min().unwrap();
+
+    // Successive shortest paths
+    loop {
+        let mut distance = vec![inf; sink + 1];
+        let mut via = vec![None; sink + 1];
+        let mut heap = BinaryHeap::new();
+        distance[source] = 0;
+        heap.push(Reverse((0, source)));
+
+        while let Some(Reverse((d, node))) = heap.pop() {
+            if d > distance[node] { continue }
+            for &e in &graph[node] {
+                let edge = &edges[e];
+                if edge.cap <= 0 { continue }
+                let next = d + edge.cost + potential[node] - potential[edge.to];
+                if next < distance[edge.to] {
+                    distance[edge.to] = next;
+                    via[edge.to] = Some(e);
+                    heap.push(Reverse((next, edge.to)));
+                }
+            }
+        }
+
+        if distance[sink] == inf { break }
+        for node in 0..sink + 1 {
+            if distance[node] < inf { potential[node] += distance[node] }
+        }
+
+        let mut node = sink;
+        while let Some(e) = via[node] {
+            edges[e].cap -= 1;
+            edges[e ^ 1].cap += 1;
+            node = edges[e ^ 1].to;
+        }
+    }
+
+    (0..agents).map(|agent| {
+        graph[agent + 1].iter()
+            .map(|&e| &edges[e])
+            .find(|edge| edge.to > agents && edge.to < sink && edge.cap == 0)
+            .map(|edge| edge.to - agents - 1)
+    }).collect::<Vec<_>>()
+}
+
+/// Forward auction solver that keeps prices and assignments between calls,
+/// so each turn starts from the previous turn's equilibrium.
+/// Agents get a discount of `penalty` on their previous task so they don't flip
+/// between near-equal targets. Results are within one unit of cost per agent of optimal.
+#[derive(Debug)]
+pub struct Auction<A: Copy + Eq + Hash, T: Copy + Eq + Hash> {
+    penalty: i64,
+    prices: FnvHashMap<(T, usize), i64>,
+    previous: FnvHashMap<A, T>,
+}
+
+impl<A: Copy + Eq + Hash, T: Copy + Eq + Hash> Auction<A, T> {
+    pub fn new(penalty: i64) -> Self {
+        Auction { penalty, prices: FnvHashMap::default(), previous: FnvHashMap::default() }
+    }
+
+    /// Assigns `agents` to `tasks` as in `assign`, warm-started from the last call.
+    pub fn solve<C: Cost>(&mut self, agents: &Vec<A>, tasks: &Vec<T>,
+        costs: &Vec<Vec<C>>, capacities: &Vec<usize>) -> Vec<Option<usize>> {
+
+        if agents.len() == 0 || tasks.len() == 0 {
+            self.previous.clear();
+            return vec![None; agents.len()]
+        }
+
+        // Expand tasks into unit-capacity slots, keeping prices for known slots
+        let slots = (0..tasks.len())
+            .flat_map(|task| (0..capacities[task]).map(move |copy| (task, copy)))
+            .collect::<Vec<_>>();
+        let mut prices = slots.iter()
+            .map(|&(task, copy)| *self.prices.get(&(tasks[task], copy)).unwrap_or(&0))
+            .collect::<Vec<_>>();
+
+        // Benefit is negated cost, with a discount for keeping the previous task
+        let mut worst = 0;
+        let benefit = agents.iter().enumerate().map(|(agent, id)| {
+            let previous = self.previous.get(id);
+            slots.iter().map(|&(task, _)| {
+                let cost = costs[agent][task].into();
+                worst = i64::max(worst, cost);
+                let discount = if previous == Some(&tasks[task]) { self.penalty } else { 0 };
+                discount - cost
+            }).collect::<Vec<_>>()
+        }).collect::<Vec<_>>();
+
+        // Staying unassigned is always an option, but worse than any task
+        let unassigned = -4 * (worst + self.penalty + 1);
+        let mut owner = vec![None; slots.len()];
+        let mut assigned = vec![None; agents.len()];
+
+        // Only relative prices matter between slots, but not against staying unassigned
+        let floor = prices.iter().cloned().min().unwrap_or(0);
+        prices.iter_mut().for_each(|price| *price -= floor);
+
+        // Reclaim previous slots where still nearly optimal
+        for (agent, id) in agents.iter().enumerate() {
+            let task = match self.previous.get(id).and_then(|task| tasks.iter().position(|other| other == task)) {
+                Some(task) => task,
+                None => continue,
+            };
+            let slot = (0..slots.len()).find(|&slot| slots[slot].0 == task && owner[slot].is_none());
+            if let Some(slot) = slot {
+                let best = (0..slots.len())
+                    .map(|other| benefit[agent][other] - prices[other])
+                    .fold(unassigned, i64::max);
+                if benefit[agent][slot] - prices[slot] + 1 >= best {
+                    owner[slot] = Some(agent);
+                    assigned[agent] = Some(slot);
+                }
+            }
+        }
+
+        // Bid until every agent holds a slot or prefers staying unassigned
+        let mut queue = (0..agents.len())
+            .filter(|&agent| assigned[agent].is_none())
+            .collect::<Vec<_>>();
+
+        while let Some(agent) = queue.pop() {
+            let mut first = (None, unassigned);
+            let mut second = unassigned;
+            for slot in 0..slots.len() {
+                let value = benefit[agent][slot] - prices[slot];
+                if value > first.1 {
+                    second = first.1;
+                    first = (Some(slot), value);
+                } else if value > second {
+                    second = value;
+                }
+            }
+
+            if let (Some(slot), value) = first {
+                prices[slot] += value - second + 1;
+                if let Some(other) = owner[slot] {
+                    assigned[other] = None;
+                    queue.push(other);
+                }
+                owner[slot] = Some(agent);
+                assigned[agent] = Some(slot);
+            }
+        }
+
+        // Unclaimed slots fall back to the floor so they stay attractive next turn
+        let floor = (0..slots.len())
+            .filter(|&slot| owner[slot].is_some())
+            .map(|slot| prices[slot])
+            .min()
+            .unwrap_or(0);
+        self.prices.clear();
+        for (slot, &(task, copy)) in slots.iter().enumerate() {
+            let price = if owner[slot].is_some() { prices[slot] - floor } else { 0 };
+            self.prices.insert((tasks[task], copy), price);
+        }
+
+        self.previous.clear();
+        let assignment = assigned.iter()
+            .map(|slot| slot.map(|slot| slots[slot].0))
+            .collect::<Vec<_>>();
+        for (agent, task) in assignment.iter().enumerate() {
+            if let Some(task) = *task {
+                self.previous.insert(agents[agent], tasks[task]);
+            }
+        }
+        assignment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solver::*;
+
+    fn cost(matrix: &Vec<Vec<i32>>) -> i32 {
+        hungarian(matrix).into_iter()
+            .enumerate()
+            .filter_map(|(row, col)| col.map(|col| matrix[row][col]))
+            .sum()
+    }
+
+    /// Columns used by the assignment, sorted, so a full matching reads as `0..n`.
+    fn columns(matrix: &Vec<Vec<i32>>) -> Vec<usize> {
+        let mut columns = hungarian(matrix).into_iter()
+            .map(|col| col.unwrap())
+            .collect::<Vec<_>>();
+        columns.sort();
+        columns
+    }
+
+    #[test]
+    fn test_basic() {
+        let matrix = vec![
+            vec![1, 1, 1],
+            vec![1, 1, 1],
+            vec![1, 1, 1],
+        ];
+        assert_eq!(columns(&matrix), vec![0, 1, 2]);
+        assert_eq!(cost(&matrix), 3);
+    }
+
+    #[test]
+    fn test_sales() {
+        let matrix = vec![
+            vec![250, 400, 350],
+            vec![400, 600, 350],
+            vec![200, 400, 250],
+        ];
+        assert_eq!(hungarian(&matrix), vec![Some(1), Some(2), Some(0)]);
+        assert_eq!(cost(&matrix), 950);
+    }
+
+    #[test]
+    fn test_wiki() {
+        let matrix = vec![
+            vec![0, 1, 2, 3],
+            vec![4, 5, 6, 0],
+            vec![0, 2, 4, 5],
+            vec![3, 0, 0, 9],
+        ];
+        assert_eq!(hungarian(&matrix), vec![Some(1), Some(3), Some(0), Some(2)]);
+        assert_eq!(cost(&matrix), 1);
+    }
+
+    #[test]
+    fn test_bulldozer() {
+        let matrix = vec![
+            vec![90, 75, 75, 80],
+            vec![35, 85, 55, 65],
+            vec![125, 95, 90, 105],
+            vec![45, 110, 95, 115],
+        ];
+        assert_eq!(columns(&matrix), vec![0, 1, 2, 3]);
+        assert_eq!(cost(&matrix), 275);
+    }
+
+    #[test]
+    fn test_stack() {
+        let matrix = vec![
+            vec![2, 9, 2, 7, 1],
+            vec![6, 8, 7, 6, 1],
+            vec![4, 6, 5, 3, 1],
+            vec![4, 2, 7, 3, 1],
+            vec![5, 3, 9, 5, 1],
+        ];
+        assert_eq!(columns(&matrix), vec![0, 1, 2, 3, 4]);
+        assert_eq!(cost(&matrix), 13);
+    }
+
+    #[test]
+    fn test_large() {
+        let matrix = vec![
+            vec![1,5,5,2,3,1,2,3,2,4,5,2,3,1,5,5,2,3,1,5,1,4,3,2,5],
+            vec![5,5,3,2,3,2,5,1,4,3,2,5,3,2,4,5,2,5,2,1,1,4,1,2,5],
+            vec![5,1,4,3,2,5,1,1,4,1,2,5,2,2,3,4,1,4,5,3,2,4,5,2,5],
+            vec![1,1,4,1,2,5,3,2,4,5,2,5,5,5,1,5,1,5,5,2,2,3,4,1,4],
+            vec![3,2,4,5,2,5,2,2,3,4,1,4,5,4,2,1,3,2,5,5,5,1,5,1,5],
+            vec![2,2,3,4,1,4,5,5,1,5,1,5,5,5,2,5,5,1,4,5,4,2,1,3,2],
+            vec![5,5,1,5,1,5,5,5,3,2,3,2,1,5,5,1,5,1,5,5,5,2,5,5,1],
+            vec![5,4,2,1,3,2,5,1,4,3,2,5,5,5,4,2,1,3,2,5,1,4,3,2,5],
+            vec![5,5,2,5,5,1,1,1,4,1,2,5,1,5,5,2,5,5,1,1,1,4,1,2,5],
+            vec![2,4,5,3,4,2,3,2,4,5,2,5,2,2,4,5,3,4,2,3,2,4,5,2,5],
+            vec![2,2,5,5,1,3,2,2,3,4,1,4,2,2,2,5,5,1,3,2,2,3,4,1,4],
+            vec![4,1,5,4,5,3,5,5,1,5,1,5,5,4,1,5,4,5,3,5,5,1,5,1,5],
+            vec![5,1,4,3,2,5,3,2,4,5,2,5,5,5,1,4,3,2,5,3,2,4,5,2,5],
+            vec![1,1,4,1,2,5,2,2,3,4,1,4,1,1,1,4,1,2,5,2,2,3,4,1,4],
+            vec![3,2,4,5,2,5,5,5,1,5,1,5,4,3,2,4,5,2,5,5,5,1,5,1,5],
+            vec![2,2,3,4,1,4,5,4,2,1,3,2,1,2,2,3,4,1,4,5,4,2,1,3,2],
+            vec![5,5,1,5,1,5,5,5,2,5,5,1,2,5,5,1,5,1,5,5,5,2,5,5,1],
+            vec![5,1,4,3,2,5,3,5,1,4,3,2,5,3,5,2,2,3,5,2,2,3,2,5,3],
+            vec![3,4,1,4,1,1,1,1,1,4,1,2,5,5,1,4,3,2,5,1,4,1,2,5,2],
+            vec![1,5,5,2,3,1,5,3,2,4,5,2,5,1,1,4,1,2,5,2,4,5,2,5,5],
+            vec![5,5,3,2,3,2,2,2,2,3,4,1,4,3,2,4,5,2,5,2,3,4,1,4,3],
+            vec![5,1,4,3,2,5,2,5,5,1,5,1,5,2,2,3,4,1,4,5,1,5,1,5,5],
+            vec![1,1,4,1,2,5,2,5,4,2,1,3,2,5,5,1,5,1,5,4,2,1,3,2,1],
+            vec![3,2,4,5,2,5,1,5,5,2,5,5,1,5,4,2,1,3,2,5,2,5,5,1,3],
+            vec![2,2,3,4,1,4,1,2,4,5,3,4,2,5,5,2,5,5,1,4,5,3,4,2,2],
+        ];
+        assert_eq!(columns(&matrix), (0..25).collect::<Vec<_>>());
+        assert_eq!(cost(&matrix), 26);
+    }
+
+    #[test]
+    fn test_c() {
+        let matrix = vec![
+            vec![0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+            vec![53,207,256,207,231,348,348,348,231,244,244,0,0,0],
+            vec![240,33,67,33,56,133,133,133,56,33,33,0,0,0],
+            vec![460,107,200,107,122,324,324,324,122,33,33,0,0,0],
+            vec![167,340,396,340,422,567,567,567,422,442,442,0,0,0],
+            vec![167,367,307,367,433,336,336,336,433,158,158,0,0,0],
+            vec![160,20,37,20,31,70,70,70,31,22,22,0,0,0],
+            vec![200,307,393,307,222,364,364,364,222,286,286,0,0,0],
+            vec![33,153,152,153,228,252,252,252,228,78,78,0,0,0],
+            vec![93,140,185,140,58,118,118,118,58,44,44,0,0,0],
+            vec![0,7,22,7,19,58,58,58,19,0,0,0,0,0],
+            vec![67,153,241,153,128,297,297,297,128,39,39,0,0,0],
+            vec![73,253,389,253,253,539,539,539,253,36,36,0,0,0],
+            vec![73,267,270,267,322,352,352,352,322,231,231,0,0,0],
+        ];
+        assert_eq!(columns(&matrix), (0..14).collect::<Vec<_>>());
+        assert_eq!(cost(&matrix), 828);
+    }
+
+    #[test]
+    fn test_optimal() {
+        let matrix = vec![
+            vec![90, 75, 75, 80],
+            vec![35, 85, 55, 65],
+            vec![125, 95, 90, 105],
+            vec![45, 110, 95, 115],
+        ];
+        assert_eq!(cost(&matrix), 275);
+    }
+
+    #[test]
+    fn test_rectangular() {
+        let matrix = vec![
+            vec![4, 1, 3],
+            vec![2, 0, 5],
+            vec![3, 2, 2],
+            vec![1, 9, 9],
+        ];
+        let assignment = hungarian(&matrix);
+        assert_eq!(assignment, vec![None, Some(1), Some(2), Some(0)]);
+        assert_eq!(cost(&matrix), 3);
+        assert_eq!(hungarian(&vec![vec![1, 2, 3]]), vec![Some(0)]);
+    }
+
+    #[test]
+    fn test_wide() {
+        let matrix = vec![
+            vec![40000, 40000, 1],
+            vec![40000, 2, 40000],
+        ];
+        assert_eq!(cost(&matrix), 3);
+    }
+
+    #[test]
+    fn test_wikihow() {
+        let matrix = vec![
+            vec![10, 19, 8, 15, 19],
+            vec![10, 18, 7, 17, 19],
+            vec![13, 16, 9, 14, 19],
+            vec![12, 19, 8, 18, 19],
+            vec![14, 17, 10, 19, 19]
+        ];
+        assert_eq!(hungarian(&matrix), vec![Some(0), Some(2), Some(3), Some(4), Some(1)]);
+        assert_eq!(cost(&matrix), 67);
+    }
+
+    #[test]
+    fn test_capacities() {
+        let costs = vec![
+            vec![1, 5, 9],
+            vec![1, 4, 9],
+            vec![1, 6, 2],
+            vec![2, 9, 9],
+        ];
+        let assignment = assign(&costs, &vec![2, 1, 1]);
+        assert_eq!(assignment, vec![Some(0), Some(1), Some(2), Some(0)]);
+    }
+
+    #[test]
+    fn test_overfull() {
+        let costs = vec![
+            vec![3, 1],
+            vec![1, 3],
+            vec![2, 2],
+        ];
+        let assignment = assign(&costs, &vec![1, 1]);
+        assert_eq!(assignment, vec![Some(1), Some(0), None]);
+        assert_eq!(assign(&costs, &vec![0, 0]), vec![None, None, None]);
+    }
+
+    #[test]
+    fn test_auction() {
+        let costs = vec![
+            vec![1, 5, 9],
+            vec![1, 4, 9],
+            vec![1, 6, 2],
+            vec![2, 9, 9],
+        ];
+        let capacities = vec![2, 1, 1];
+        let agents = vec![10, 11, 12, 13];
+        let tasks = vec!['a', 'b', 'c'];
+        let mut auction = Auction::new(0);
+        let cold = auction.solve(&agents, &tasks, &costs, &capacities);
+        let warm = auction.solve(&agents, &tasks, &costs, &capacities);
+        assert_eq!(cold, assign(&costs, &capacities));
+        assert_eq!(warm, cold);
+    }
+
+    #[test]
+    fn test_switching() {
+        let agents = vec![0];
+        let tasks = vec![0, 1];
+        let mut auction = Auction::new(3);
+        assert_eq!(auction.solve(&agents, &tasks, &vec![vec![4, 5]], &vec![1, 1]), vec![Some(0)]);
+        assert_eq!(auction.solve(&agents, &tasks, &vec![vec![6, 5]], &vec![1, 1]), vec![Some(0)]);
+        assert_eq!(auction.solve(&agents, &tasks, &vec![vec![10, 5]], &vec![1, 1]), vec![Some(1)]);
+        // The discount now keeps it on the new task even where the old one is cheaper
+        assert_eq!(auction.solve(&agents, &tasks, &vec![vec![4, 5]], &vec![1, 1]), vec![Some(1)]);
+    }
+}
@@ -0,0 +1,170 @@
+use fnv::{FnvHashMap, FnvHashSet};
+
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+use engine::world::*;
+use navigate::*;
+
+const SCOUT_AFTER: usize = 6;
+const DEFEND_SHARE: usize = 5;
+const ESCORTS: usize = 2;
+const ESCORT_TERM: Rounds = 100;
+const IDLE_LIMIT: u32 = 10;
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+/// What a squad is for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Role {
+    Attack,
+    Defend,
+    Escort,
+    Scout,
+    Board,
+}
+
+/// What we remember about a unit between turns.
+#[derive(Clone, Debug)]
+struct Member {
+    /// Current squad; `Board` while the unit is assigned to a rocket.
+    role: Role,
+    /// Squad the unit goes back to after boarding falls through.
+    home: Role,
+    /// Round the unit joined its home squad.
+    since: Rounds,
+    last: MapLocation,
+    /// Turns the unit has stayed on the same tile.
+    idle: u32,
+}
+
+/// Whether a unit should leave its home squad for the main army. Escorts lapse
+/// once the rockets are gone or after a spell of guarding, since standing still
+/// is their job, while scouts stuck in place are better off attacking.
+fn lapsed(home: Role, since: Rounds, idle: u32, round: Rounds, rockets: bool) -> bool {
+    match home {
+        Role::Escort => !rockets || round >= since + ESCORT_TERM,
+        Role::Scout => idle >= IDLE_LIMIT,
+        _ => false,
+    }
+}
+
+/// Picks a squad for a new unit given how many members each squad already has.
+pub fn choose(counts: &FnvHashMap<Role, usize>, total: usize, unit_type: UnitType, rockets: bool) -> Role {
+    let count = |role| counts.get(&role).cloned().unwrap_or(0);
+    if count(Role::Scout) == 0 && total >= SCOUT_AFTER && unit_type == UnitType::Ranger {
+        Role::Scout
+    } else if count(Role::Defend) < total / DEFEND_SHARE {
+        Role::Defend
+    } else if rockets && count(Role::Escort) < ESCORTS && unit_type != UnitType::Healer {
+        Role::Escort
+    } else {
+        Role::Attack
+    }
+}
+
+/// Groups units into squads by role and keeps their state across turns.
+#[derive(Debug)]
+pub struct Squads {
+    members: FnvHashMap<UnitID, Member>,
+    targets: FnvHashMap<Role, MapLocation>,
+}
+
+impl Squads {
+    pub fn new() -> Self {
+        Squads { members: FnvHashMap::default(), targets: FnvHashMap::default() }
+    }
+
+    /// Adds new units, forgets dead ones and moves boarding units into the `Board` squad.
+    pub fn update(&mut self, gc: &GameController, units: &Vec<Unit>,
+        boarding: &FnvHashMap<UnitID, MapLocation>, rockets: bool) {
+
+        let alive = units.iter().map(|unit| unit.id()).collect::<FnvHashSet<_>>();
+        self.members.retain(|id, _| alive.contains(id));
+
+        for unit in units.iter().filter(|unit| unit.location().is_on_map()) {
+            let location = loc(unit);
+            if !self.members.contains_key(&unit.id()) {
+                let role = choose(&self.counts(), self.members.len() + 1, unit.unit_type(), rockets);
+                self.members.insert(unit.id(), Member { role, home: role, since: gc.round(), last: location, idle: 0 });
+            }
+
+            let member = self.members.get_mut(&unit.id()).unwrap();
+            member.idle = if member.last == location { member.idle + 1 } else { 0 };
+            member.last = location;
+            if lapsed(member.home, member.since, member.idle, gc.round(), rockets) {
+                member.home = Role::Attack;
+                member.since = gc.round();
+                member.idle = 0;
+            }
+            member.role = if boarding.contains_key(&unit.id()) { Role::Board } else { member.home };
+        }
+    }
+
+    /// Squad sizes by home role.
+    pub fn counts(&self) -> FnvHashMap<Role, usize> {
+        let mut counts = FnvHashMap::default();
+        for member in self.members.values() {
+            *counts.entry(member.home).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn role(&self, id: UnitID) -> Option<Role> {
+        self.members.get(&id).map(|member| member.role)
+    }
+
+    /// Sets where a squad should head when its members have nothing to fight.
+    pub fn order(&mut self, role: Role, target: Option<MapLocation>) {
+        match target {
+            Some(target) => { self.targets.insert(role, target); }
+            None => { self.targets.remove(&role); }
+        }
+    }
+
+    pub fn target(&self, id: UnitID) -> Option<MapLocation> {
+        self.role(id).and_then(|role| self.targets.get(&role).cloned())
+    }
+
+    /// Hands a unit's squad order to the navigator; false if its squad has none.
+    pub fn advance(&self, nav: &mut Navigator, unit: &Unit) -> bool {
+        match self.target(unit.id()) {
+            Some(target) => {
+                nav.navigate(unit, &target);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use squad::*;
+
+    #[test]
+    fn test_choose() {
+        let mut counts = FnvHashMap::default();
+        assert_eq!(choose(&counts, 1, UnitType::Knight, false), Role::Attack);
+        assert_eq!(choose(&counts, 5, UnitType::Knight, false), Role::Defend);
+
+        counts.insert(Role::Defend, 1);
+        assert_eq!(choose(&counts, 6, UnitType::Ranger, false), Role::Scout);
+        assert_eq!(choose(&counts, 6, UnitType::Knight, true), Role::Escort);
+
+        counts.insert(Role::Escort, 2);
+        assert_eq!(choose(&counts, 6, UnitType::Knight, true), Role::Attack);
+    }
+
+    #[test]
+    fn test_lapsed() {
+        assert!(!lapsed(Role::Escort, 10, 0, 50, true));
+        assert!(lapsed(Role::Escort, 10, 0, 50, false));
+        assert!(lapsed(Role::Escort, 10, 0, 110, true));
+        assert!(!lapsed(Role::Escort, 10, IDLE_LIMIT, 50, true));
+        assert!(lapsed(Role::Scout, 10, IDLE_LIMIT, 50, false));
+        assert!(!lapsed(Role::Defend, 10, IDLE_LIMIT, 500, false));
+    }
+}
@@ -0,0 +1,73 @@
+use engine::controller::*;
+use engine::location::*;
+use engine::unit::*;
+
+/// A worker's job for the turn: where it heads and what it does on arrival.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Task {
+    Harvest(MapLocation),
+    Build(MapLocation),
+    Repair(MapLocation),
+    Blueprint(UnitType, MapLocation),
+    Board(MapLocation),
+    Flee(MapLocation),
+}
+
+fn loc(unit: &Unit) -> MapLocation {
+    unit.location().map_location().unwrap()
+}
+
+impl Task {
+    pub fn target(&self) -> MapLocation {
+        match *self {
+            Task::Harvest(location)
+            | Task::Build(location)
+            | Task::Repair(location)
+            | Task::Blueprint(_, location)
+            | Task::Board(location)
+            | Task::Flee(location) => location,
+        }
+    }
+
+    /// Carries out the task if the worker is in range, returning whether it acted.
+    pub fn perform(&self, gc: &mut GameController, worker: &Unit) -> bool {
+        let id = worker.id();
+        let worker_loc = loc(worker);
+        match *self {
+            Task::Harvest(location) => {
+                let direction = worker_loc.direction_to(location);
+                if worker_loc.distance_squared_to(location) <= 2 && gc.can_harvest(id, direction) {
+                    gc.harvest(id, direction);
+                    return true
+                }
+            }
+            Task::Build(location) => {
+                if let Ok(building) = gc.sense_unit_at_location(location) {
+                    if gc.can_build(id, building.id()) {
+                        gc.build(id, building.id());
+                        return true
+                    }
+                }
+            }
+            Task::Repair(location) => {
+                if let Ok(building) = gc.sense_unit_at_location(location) {
+                    if gc.can_repair(id, building.id()) {
+                        gc.repair(id, building.id());
+                        return true
+                    }
+                }
+            }
+            Task::Blueprint(building_type, location) => {
+                if worker_loc.is_adjacent_to(location) {
+                    let direction = worker_loc.direction_to(location);
+                    if gc.can_blueprint(id, building_type, direction) {
+                        gc.blueprint(id, building_type, direction);
+                        return true
+                    }
+                }
+            }
+            Task::Board(_) | Task::Flee(_) => (),
+        }
+        false
+    }
+}